@@ -55,6 +55,10 @@ export function send_ws(data) {
   window.exosphere.websocket.send(data);
 }
 
+export function now() {
+  return performance.now();
+}
+
 export function get_input_value(id) {
   return document.getElementById(id).innerText;
 }
@@ -128,6 +132,7 @@ export function setup_placemenu_row(index) {
 
 export function add_placemenu_item(row, id, asset, name, description, cost) {
   let el = document.createElement("div");
+  el.id = "placeitem_" + row + "_" + id;
   let inp = document.createElement("input");
   inp.className = "picker_radio";
   inp.type = "radio";
@@ -160,6 +165,10 @@ export function add_placemenu_item(row, id, asset, name, description, cost) {
   el.appendChild(dbar);
 }
 
+export function set_placemenu_item_available(row, id, available) {
+  document.getElementById("placeitem_" + row + "_" + id).classList.toggle("unavailable", !available);
+}
+
 export function clear_piecepicker() {
   for (el of document.getElementsByClassName("picker_radio")) {
     el.checked = false;
@@ -217,3 +226,31 @@ export function set_teams_select(teams) {
     teams_select.appendChild(option);
   }
 }
+
+export function add_chat_line(channel, from, text) {
+  let log = document.getElementById("chatlog");
+  let line = document.createElement("div");
+  let tag = document.createElement("span");
+  tag.className = "chat-" + channel;
+  tag.innerText = "[" + channel + "] " + from + ": ";
+  line.appendChild(tag);
+  line.appendChild(document.createTextNode(text)); // never innerHTML: this is other people's text
+  log.appendChild(line);
+  while (log.childElementCount > 100) {
+    log.removeChild(log.firstChild);
+  }
+  log.scrollTop = log.scrollHeight;
+}
+
+let notice_timeout = null;
+
+export function show_notice(text) {
+  // a short-lived message across the top of the screen, for things like the server refusing to place something
+  let notice = document.getElementById("notice");
+  notice.innerText = text;
+  notice.style.display = "block";
+  clearTimeout(notice_timeout);
+  notice_timeout = setTimeout(() => {
+    notice.style.display = "none";
+  }, 3000);
+}
@@ -57,30 +57,85 @@ function setup(state) {
       document.getElementById("password").innerText,
     );
   };
+  document.getElementById("chat-input").onkeydown = (evt) => {
+    if (evt.key == "Enter") {
+      let input = document.getElementById("chat-input");
+      window.exosphere.state.on_chat_submit(
+        input.value,
+        document.getElementById("chat-channel").value,
+      );
+      input.value = "";
+      input.blur();
+    } else if (evt.key == "Escape") {
+      document.getElementById("chat-input").blur();
+    }
+  };
   document.getElementById("team-submit").onclick = () => {
     window.exosphere.state.on_team_submit(
       document.getElementById("team-password").innerText,
       document.getElementById("team-chooser").value,
     );
   };
-  document.getElementById("play").onclick = () => {
+  let started = false;
+  let reconnects = 0;
+  const connect = () => {
     let websocket = new WebSocket(document.getElementById("server").innerText);
     websocket.onopen = () => {
-      mainloop();
+      reconnects = 0;
+      if (!started) {
+        started = true;
+        mainloop();
+      }
     };
     websocket.onerror = () => {
-      alert("connection error");
-      window.location.reload();
+      // once we're in the game, onclose decides whether to keep trying
+      if (!started) {
+        alert("connection error");
+        window.location.reload();
+      }
+    };
+    websocket.onclose = (evt) => {
+      // 1006 (dropped) and 1001 (going away) mean the connection was lost rather than closed on purpose:
+      // try to pick up where we left off. the server holds onto our pieces for a little while
+      if (
+        started &&
+        (evt.code == 1006 || evt.code == 1001) &&
+        reconnects < 5 &&
+        window.exosphere.state.on_connection_lost()
+      ) {
+        reconnects++;
+        setTimeout(connect, 1000 * reconnects);
+        return;
+      }
+      // 1000 is a normal close (game over); anything else is worth telling the player about
+      if (evt.code != 1000 && evt.reason) {
+        alert("disconnected: " + evt.reason);
+      } else if (evt.code != 1000) {
+        alert("lost connection to the server");
+      }
     };
     websocket.onmessage = async (msg) => {
       let bytes = new Uint8Array(await msg.data.arrayBuffer());
       window.exosphere.state.on_message(bytes);
     };
     window.exosphere.websocket = websocket;
+  };
+  document.getElementById("play").onclick = () => {
+    connect();
     window.addEventListener("keydown", (evt) => {
+      if (evt.target.id == "chat-input") {
+        return; // typing isn't steering
+      }
+      if (evt.key == "Enter") {
+        document.getElementById("chat-input").focus();
+        return;
+      }
       window.exosphere.state.key_down(evt.key);
     });
     window.addEventListener("keyup", (evt) => {
+      if (evt.target.id == "chat-input") {
+        return;
+      }
       window.exosphere.state.key_up(evt.key);
     });
     window.addEventListener("pointermove", (evt) => {
@@ -99,6 +154,7 @@ function setup(state) {
     window.exosphere.canvas.height = window.innerHeight;
     document.getElementById("grid-overlay").width = window.innerWidth;
     document.getElementById("grid-overlay").height = window.innerHeight;
+    window.exosphere.state.set_screen_size(window.innerWidth, window.innerHeight);
   }
 
   onresize();
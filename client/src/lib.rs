@@ -11,26 +11,29 @@
 */
 
 use common::comms::*;
+use common::fab::{FabLevels, TechLine};
 use common::pathfollower::{PathFollower, PathIter, PathNode};
 use common::steal_mut;
 use common::types::PieceType;
-use common::VERSION;
 use common::{PieceId, PlayerId};
+use common::{MAGIC, PROTOCOL, UPDATE_RATE};
 use num_traits::cast::FromPrimitive;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU32, Ordering};
 use wasm_bindgen::prelude::*;
 // TODO: refactor this whole thing to use bevy
 // pretty important
 // right now this code is unstable, hard to understand, and calls common::steal_mut *multiple times*.
 // it is essentially impossible to maintain in any coherent way. a move to ECS is necessary.
 
-const PLACE_MENU: [&'static [PieceType]; 4] = [
+const PLACE_MENU: [&'static [PieceType]; 5] = [
     &[
         PieceType::BallisticMissile,
         PieceType::SeekingMissile,
         PieceType::HypersonicMissile,
         PieceType::TrackingMissile,
         PieceType::CruiseMissile,
+        PieceType::Madecs,
     ], // missiles
     &[
         PieceType::BasicFighter,
@@ -50,6 +53,12 @@ const PLACE_MENU: [&'static [PieceType]; 4] = [
         PieceType::LaserTurret,
         PieceType::EmpZone,
     ], // defense
+    &[
+        PieceType::OutpostConstructor,
+        PieceType::TeleportalConstructor,
+        PieceType::FortConstructor,
+        PieceType::CastleConstructor,
+    ], // buildings
 ];
 
 #[derive(Debug)]
@@ -106,6 +115,7 @@ extern "C" {
     fn ctx_line_between(x1: f32, y1: f32, x2: f32, y2: f32);
     fn setup_placemenu_row(index: usize);
     fn add_placemenu_item(row: usize, item: u16, img: &str, name: &str, desc: &str, cost: u32);
+    fn set_placemenu_item_available(row: usize, item: u16, available: bool);
     fn clear_piecepicker();
     fn ctx_alpha(alpha: f32);
     fn ctx_fill_rect(x: f32, y: f32, w: f32, h: f32);
@@ -113,12 +123,22 @@ extern "C" {
     fn draw_text_box(x: f32, y: f32, lines: Vec<String>);
     fn screen(scr: &str);
     fn set_teams_select(teams: Vec<TeamDescriptor>);
+    fn add_chat_line(channel: &str, from: &str, text: &str);
+    fn now() -> f64; // milliseconds, from performance.now()
+    fn show_notice(text: &str);
 }
 
 fn send(message: ClientMessage) {
     send_ws(bitcode::encode(&message));
 }
 
+// every command gets an id of its own, so when the server turns one down we know which
+static NEXT_REQUEST_ID: AtomicU32 = AtomicU32::new(1);
+
+fn request_id() -> u32 {
+    NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed)
+}
+
 struct PlayerData {
     id: PlayerId,
     slot: u8,
@@ -148,6 +168,7 @@ struct TerritoryData {
 
 struct FabberData {
     radius: f32,
+    levels: FabLevels,
 }
 
 struct ObjectData {
@@ -172,6 +193,7 @@ impl ObjectData {
         let endex = self.path.endex().unwrap();
         self.path.insert_node(endex, node);
         send(ClientMessage::Strategy {
+            request_id: request_id(),
             evt: StrategyPathModification::Insert(self.id, endex, node),
         });
     }
@@ -179,6 +201,7 @@ impl ObjectData {
     fn path_insert(&mut self, before: u16, node: PathNode) {
         self.path.insert_node(before, node);
         send(ClientMessage::Strategy {
+            request_id: request_id(),
             evt: StrategyPathModification::Insert(self.id, before, node),
         });
     }
@@ -186,6 +209,7 @@ impl ObjectData {
     fn update_strategy(&mut self, index: u16, node: PathNode) {
         self.path.update_node(index, node);
         send(ClientMessage::Strategy {
+            request_id: request_id(),
             evt: StrategyPathModification::Set(self.id, index, node),
         });
     }
@@ -193,6 +217,7 @@ impl ObjectData {
     fn delete_strategy(&mut self, index: u16) {
         self.path.remove_node(index);
         send(ClientMessage::Strategy {
+            request_id: request_id(),
             evt: StrategyPathModification::Delete(self.id, index),
         });
     }
@@ -215,11 +240,21 @@ struct State {
     inputs: InputState,
     off_x: f32,
     off_y: f32,
+    screen_width: f32,
+    screen_height: f32,
+    reported_view: Option<(f32, f32, f32, f32)>, // the last viewport we told the server about
+    fog_of_war: bool,
+    explored: HashSet<(i32, i32)>, // fog cells we've ever had in sight
     x_scroll_ramp: f32,
     y_scroll_ramp: f32,
     has_placed: bool,
+    castle_request: Option<u32>, // the request we placed our castle with. if the server turns it down, we get another go
     money: u32,
     has_tested: bool,
+    features: Features, // the optional protocol features we agreed on with the server
+    snapshots: VecDeque<(u32, HashMap<PieceId, (SnapshotMove, u32)>)>, // recent snapshots, fully rebuilt, for applying deltas against. each sample is paired with the snapshot it was taken in
+    snapshot_received: f64, // when the newest snapshot arrived, in milliseconds
+    resume_token: Option<u128>, // from the last Metadata. if the connection drops, this gets us back into the game
     territory_buf: Vec<f32>,
     fabber_buf: Vec<f32>,
     active_piece: Option<PieceId>,
@@ -231,19 +266,41 @@ struct State {
     explosions: Vec<Explosion>,
     gun_states: HashMap<PieceId, bool>,
     is_placeable: bool,
+    muted: HashSet<PlayerId>, // players whose chat we've chosen not to see
 }
 
 const SCROLL_ACC: f32 = 0.3;
 const SCROLL_BASE: f32 = 5.0;
 const SCROLL_MAX: f32 = 30.0;
+const VIEWPORT_SLACK: f32 = 100.0; // how far the view can drift before we bother telling the server
+const FOG_CELL: f32 = 100.0; // fog of war is drawn in squares this big
+const MAX_EXTRAPOLATION: f32 = 2.5; // seconds. the server resamples moving pieces every 2 seconds, so anything older means we've lost touch
 
 impl State {
-    fn place(&self, tp: PieceType) {
+    fn refresh_placemenu(&self) {
+        // grey out everything none of our (or our team's) fabbers can make
+        for row in 0..PLACE_MENU.len() {
+            for item in PLACE_MENU[row] {
+                let available = self.fabber_data.iter().any(|(id, fabber)| {
+                    self.object_data
+                        .get(id)
+                        .is_some_and(|obj| self.is_friendly(obj.owner))
+                        && fabber.levels >= item.fabber()
+                });
+                set_placemenu_item_available(row, *item as u16, available);
+            }
+        }
+    }
+
+    fn place(&self, tp: PieceType) -> u32 {
+        let request_id = request_id();
         send(ClientMessage::PlacePiece {
+            request_id,
             x: self.inputs.mouse_x,
             y: self.inputs.mouse_y,
             tp,
         });
+        request_id
     }
 
     fn overlay(&mut self) {
@@ -291,6 +348,109 @@ impl State {
         );
     }
 
+    fn extrapolate(&mut self) {
+        // pieces are drawn where their latest samples say they should be by now. extrapolating rather than interpolating
+        // means no extra delay, and the server resamples any piece that strays too far from its extrapolated path
+        let Some((sequence, frame)) = self.snapshots.back() else {
+            return;
+        };
+        let since = ((now() - self.snapshot_received) / 1000.0) as f32;
+        for (mv, at) in frame.values() {
+            if let Some(obj) = self.object_data.get_mut(&mv.id) {
+                let seconds =
+                    ((sequence - at) as f32 / UPDATE_RATE as f32 + since).min(MAX_EXTRAPOLATION);
+                let (x, y, a) =
+                    mv.extrapolate(seconds, self.gameboard_width, self.gameboard_height);
+                obj.x = x;
+                obj.y = y;
+                obj.a = a;
+            }
+        }
+    }
+
+    fn draw_fog(&mut self) {
+        // under fog of war, the server only tells us about enemies we can see. darken everything we can't: heavily
+        // where we've never looked, lightly where we have (we remember the ground, but not what's on it now)
+        if !self.fog_of_war || self.slot == 0 {
+            return; // spectators see everything
+        }
+        let mut visible = HashSet::new();
+        for obj in self.object_data.values() {
+            if !self.is_friendly(obj.owner) {
+                continue;
+            }
+            let range = obj.tp.vision();
+            let min_x = ((obj.x - range) / FOG_CELL).floor() as i32;
+            let max_x = ((obj.x + range) / FOG_CELL).floor() as i32;
+            let min_y = ((obj.y - range) / FOG_CELL).floor() as i32;
+            let max_y = ((obj.y + range) / FOG_CELL).floor() as i32;
+            for x in min_x..=max_x {
+                for y in min_y..=max_y {
+                    let dx = (x as f32 + 0.5) * FOG_CELL - obj.x;
+                    let dy = (y as f32 + 0.5) * FOG_CELL - obj.y;
+                    if dx * dx + dy * dy <= range * range {
+                        visible.insert((x, y));
+                    }
+                }
+            }
+        }
+        self.explored.extend(visible.iter().copied());
+        // only the cells on screen (and on the board) need drawing
+        let min_x = ((self.off_x - self.screen_width / 2.0) / FOG_CELL)
+            .floor()
+            .max(0.0) as i32;
+        let max_x = ((self.off_x + self.screen_width / 2.0) / FOG_CELL)
+            .ceil()
+            .min((self.gameboard_width / FOG_CELL).ceil()) as i32;
+        let min_y = ((self.off_y - self.screen_height / 2.0) / FOG_CELL)
+            .floor()
+            .max(0.0) as i32;
+        let max_y = ((self.off_y + self.screen_height / 2.0) / FOG_CELL)
+            .ceil()
+            .min((self.gameboard_height / FOG_CELL).ceil()) as i32;
+        ctx_fill("black");
+        for x in min_x..max_x {
+            for y in min_y..max_y {
+                if visible.contains(&(x, y)) {
+                    continue;
+                }
+                ctx_alpha(if self.explored.contains(&(x, y)) {
+                    0.4
+                } else {
+                    0.8
+                });
+                ctx_fill_rect(x as f32 * FOG_CELL, y as f32 * FOG_CELL, FOG_CELL, FOG_CELL);
+            }
+        }
+        ctx_alpha(1.0);
+    }
+
+    fn report_viewport(&mut self) {
+        // the server only sends us what's around the viewport, so it needs to know where that is
+        let view = (
+            self.off_x - self.screen_width / 2.0,
+            self.off_y - self.screen_height / 2.0,
+            self.screen_width,
+            self.screen_height,
+        );
+        if let Some(last) = self.reported_view {
+            if (view.0 - last.0).abs() < VIEWPORT_SLACK
+                && (view.1 - last.1).abs() < VIEWPORT_SLACK
+                && view.2 == last.2
+                && view.3 == last.3
+            {
+                return;
+            }
+        }
+        self.reported_view = Some(view);
+        send(ClientMessage::Viewport {
+            x: view.0,
+            y: view.1,
+            width: view.2,
+            height: view.3,
+        });
+    }
+
     fn is_friendly(&self, other: PlayerId) -> bool {
         if other == PlayerId::SYSTEM {
             return false;
@@ -366,11 +526,21 @@ impl State {
             },
             off_x: 0.0,
             off_y: 0.0,
+            screen_width: 0.0,
+            screen_height: 0.0,
+            reported_view: None,
+            fog_of_war: false,
+            explored: HashSet::new(),
             x_scroll_ramp: 0.0,
             y_scroll_ramp: 0.0,
             has_placed: false,
+            castle_request: None,
             money: 0,
             has_tested: false,
+            features: Features::NONE,
+            snapshots: VecDeque::new(),
+            snapshot_received: 0.0,
+            resume_token: None,
             territory_buf: vec![],
             fabber_buf: vec![],
             active_piece: None,
@@ -382,6 +552,7 @@ impl State {
             explosions: vec![],
             gun_states: HashMap::new(),
             is_placeable: false,
+            muted: HashSet::new(),
         }
     }
 
@@ -406,7 +577,9 @@ impl State {
         } else {
             self.x_scroll_ramp = 0.0;
         }
+        self.extrapolate();
         self.overlay();
+        self.draw_fog();
         self.hovered = None;
         self.hovered_anything = None;
         for obj in self.object_data.values() {
@@ -499,6 +672,7 @@ impl State {
                                 let ind = obj.path.len().unwrap() - 1;
                                 steal_mut(&obj.path).update_node(ind, node);
                                 send(ClientMessage::Strategy {
+                                    request_id: request_id(),
                                     evt: StrategyPathModification::Set(obj.id, ind, node),
                                 });
                             } else {
@@ -536,6 +710,9 @@ impl State {
             }
         }
         set_offset(self.off_x, self.off_y);
+        if self.has_tested {
+            self.report_viewport();
+        }
         if self.stage == Stage::MoveShips {
             if let Some((id, index)) = self.updating_node {
                 if self.inputs.key("r") {
@@ -630,6 +807,11 @@ impl State {
         }
     }
 
+    pub fn set_screen_size(&mut self, width: f32, height: f32) {
+        self.screen_width = width;
+        self.screen_height = height;
+    }
+
     pub fn set_mouse_pos(&mut self, x: f32, y: f32) {
         self.inputs.mouse_x = x;
         self.inputs.mouse_y = y;
@@ -757,7 +939,7 @@ impl State {
         } else {
             // we don't have a castle yet! let's place that now, if possible
             // TODO: check territory stuff
-            self.place(PieceType::Castle);
+            self.castle_request = Some(self.place(PieceType::Castle));
             self.has_placed = true;
             self.piecepicker = None;
         }
@@ -783,17 +965,64 @@ impl State {
                 if let Some(mut state) = self.gun_states.get_mut(&piece) {
                     *state = !(*state);
                     send(ClientMessage::Special {
+                        request_id: request_id(),
                         id: piece,
                         evt: ObjectSpecialPropertySet::GunState(*state),
                     });
                 } else {
                     self.gun_states.insert(piece, true);
                     send(ClientMessage::Special {
+                        request_id: request_id(),
                         id: piece,
                         evt: ObjectSpecialPropertySet::GunState(true),
                     });
                 }
             }
+            // number keys buy fabber levels on a selected fabber: missiles, ships, econ, defense, buildings
+            let line = match key.as_str() {
+                "1" => Some(TechLine::Missiles),
+                "2" => Some(TechLine::Ships),
+                "3" => Some(TechLine::Econ),
+                "4" => Some(TechLine::Defense),
+                "5" => Some(TechLine::Buildings),
+                _ => None,
+            };
+            if let (Some(line), Some(fabber)) = (line, self.fabber_data.get(&piece)) {
+                let level = fabber.levels.get(line) + 1;
+                if level <= TechLine::MAX_LEVEL {
+                    show_notice(&format!(
+                        "upgrading {:?} to level {} for ${}",
+                        line,
+                        level,
+                        line.price_per_level() * level as u32
+                    ));
+                }
+                send(ClientMessage::UpgradeFabber {
+                    request_id: request_id(),
+                    id: piece,
+                    line,
+                });
+            }
+            let constructor = self
+                .object_data
+                .get(&piece)
+                .is_some_and(|obj| obj.tp.constructs().is_some());
+            if key == "c" && constructor {
+                // constructor ships stop where they are and start building
+                send(ClientMessage::Special {
+                    request_id: request_id(),
+                    id: piece,
+                    evt: ObjectSpecialPropertySet::Construct,
+                });
+            }
+            if key == "r" {
+                // fix it up completely. the server charges for it and sends the new health back
+                send(ClientMessage::Special {
+                    request_id: request_id(),
+                    id: piece,
+                    evt: ObjectSpecialPropertySet::Repair,
+                });
+            }
         }
         self.inputs.keys_down.insert(key, false);
     }
@@ -815,7 +1044,12 @@ impl State {
                     board_width,
                     board_height,
                     slot,
+                    resume_token,
+                    fog_of_war,
                 } => {
+                    self.resume_token =
+                        Some(resume_token).filter(|_| self.features.contains(Features::RESUME));
+                    self.fog_of_war = fog_of_war;
                     self.gameboard_width = board_width;
                     self.gameboard_height = board_height;
                     self.id = id;
@@ -870,8 +1104,17 @@ impl State {
                     self.territory_data
                         .insert(id, TerritoryData { radius: radius });
                 }
-                ServerMessage::Fabber { id, radius } => {
-                    self.fabber_data.insert(id, FabberData { radius: radius });
+                ServerMessage::Fabber { id, radius, levels } => {
+                    let ours = self
+                        .object_data
+                        .get(&id)
+                        .is_some_and(|obj| obj.owner == self.id);
+                    if let Some(old) = self.fabber_data.insert(id, FabberData { radius, levels }) {
+                        if ours && old.levels != levels {
+                            show_notice(&format!("fabber upgraded to {}", levels));
+                        }
+                    }
+                    self.refresh_placemenu();
                 }
                 ServerMessage::ObjectCreate {
                     x,
@@ -895,15 +1138,45 @@ impl State {
                         },
                     );
                 }
-                ServerMessage::ObjectMove { id, x, y, a } => {
-                    if let Some(obj) = self.object_data.get_mut(&id) {
-                        obj.x = x;
-                        obj.y = y;
-                        obj.a = a;
+                ServerMessage::Snapshot {
+                    sequence,
+                    baseline,
+                    moves,
+                    removed,
+                } => {
+                    let mut frame = if baseline == 0 {
+                        HashMap::new()
+                    } else if let Some((_, frame)) =
+                        self.snapshots.iter().find(|(s, _)| *s == baseline)
+                    {
+                        frame.clone()
+                    } else {
+                        return; // we don't have the baseline. don't ack, and the server will eventually send a full one
+                    };
+                    for id in removed {
+                        frame.remove(&id);
+                    }
+                    for mv in moves {
+                        frame.insert(mv.id, (mv, sequence.saturating_sub(mv.age as u32)));
+                    }
+                    if self.snapshots.len() >= 64 {
+                        self.snapshots.pop_front();
+                    }
+                    // tick() moves the pieces, by extrapolating from the newest frame
+                    self.snapshots.push_back((sequence, frame));
+                    self.snapshot_received = now();
+                    if self.features.contains(Features::SNAPSHOTS) {
+                        send(ClientMessage::SnapshotAck { sequence });
                     }
                 }
                 ServerMessage::DeleteObject { id } => {
+                    // pieces get deleted when they die, but also when they leave our area of interest
                     self.object_data.remove(&id);
+                    self.territory_data.remove(&id);
+                    if self.fabber_data.remove(&id).is_some() {
+                        self.refresh_placemenu();
+                    }
+                    self.gun_states.remove(&id);
                 }
                 ServerMessage::Health { id, health } => {
                     if let Some(obj) = self.object_data.get_mut(&id) {
@@ -917,6 +1190,7 @@ impl State {
                             alert(&format!("error! mismatched strategy paths {} (local) vs {} (server)! attempting recovery", obj.path.len().unwrap(), remaining));
                             obj.path.clear();
                             send(ClientMessage::Strategy {
+                                request_id: request_id(),
                                 evt: StrategyPathModification::Clear(id),
                             });
                         }
@@ -950,9 +1224,9 @@ impl State {
                         age: 2,
                     });
                 }
-                ServerMessage::Disconnect => {
+                ServerMessage::Disconnect { reason: _ } => {
                     // the server is signalling that we will be disconnected. we don't get a choice in the matter
-                    // eventually this might do something on the client side; for now it's a no-op
+                    // the reason also comes through in the close frame, which setup.js reports
                 }
                 ServerMessage::Winner { id } => {
                     // TODO: win screen
@@ -968,6 +1242,38 @@ impl State {
                     alert("you lost");
                     reload();
                 }
+                ServerMessage::ChatMessage {
+                    from,
+                    channel,
+                    text,
+                } => {
+                    if self.muted.contains(&from) {
+                        return;
+                    }
+                    let from = if from == PlayerId::SYSTEM {
+                        "server".to_string()
+                    } else if let Some(player) = self.player_data.get(&from) {
+                        player.name.clone()
+                    } else {
+                        format!("{:?}", from)
+                    };
+                    add_chat_line(channel.get_str(), &from, &text);
+                }
+                ServerMessage::CommandRejected { request_id, reason } => {
+                    if self.castle_request == Some(request_id) {
+                        self.has_placed = false;
+                    }
+                    show_notice(reason.get_str());
+                }
+                ServerMessage::ResumeFailed => {
+                    // too late: our pieces are gone. start over as a new player
+                    self.resume_token = None;
+                    self.has_placed = false;
+                    self.money = 0;
+                    send(ClientMessage::Connect {
+                        nickname: get_input_value("nickname"),
+                    });
+                }
                 ServerMessage::Reject => {
                     alert("invalid password!");
                     reload();
@@ -977,44 +1283,105 @@ impl State {
                 }
             }
         } else {
-            if let ServerMessage::Test(
-                ref exostring,
-                128,
-                4096,
-                115600,
-                123456789012345,
-                -64,
-                -4096,
-                -115600,
-                -123456789012345,
-                -4096.512,
-                -8192.756,
-                VERSION,
-            ) = message
-            {
-                if exostring == "EXOSPHERE" {
-                    send(ClientMessage::Test(
-                        "EXOSPHERE".to_string(),
-                        128,
-                        4096,
-                        115600,
-                        123456789012345,
-                        -64,
-                        -4096,
-                        -115600,
-                        -123456789012345,
-                        -4096.512,
-                        -8192.756,
-                        VERSION,
-                    ));
-                    send(ClientMessage::Connect {
-                        nickname: get_input_value("nickname"),
-                    });
-                    self.has_tested = true;
+            let ServerMessage::Hello {
+                magic,
+                protocol,
+                features,
+            } = message
+            else {
+                alert(&format!("server failed verification: {:?}", message));
+                return;
+            };
+            if magic != MAGIC {
+                alert("that isn't an exosphere server");
+                return;
+            }
+            if protocol.major != PROTOCOL.major {
+                alert(&format!(
+                    "this server speaks protocol {}, but this client speaks {}. try reloading",
+                    protocol, PROTOCOL
+                ));
+                return;
+            }
+            // everything we know how to use, out of what's on offer. the server checks our version; if it's older
+            // than we are, it'll close the connection and say why
+            self.features = features & (Features::SNAPSHOTS | Features::CHAT | Features::RESUME);
+            send(ClientMessage::Hello {
+                magic: MAGIC.to_string(),
+                protocol: PROTOCOL,
+                features: self.features,
+            });
+            // before anything else, so the server doesn't start by sending us the whole board
+            self.report_viewport();
+            // the server we reconnected to might not offer resume any more. it would ignore a Resume and leave us hanging
+            if !self.features.contains(Features::RESUME) {
+                self.resume_token = None;
+            }
+            if let Some(token) = self.resume_token {
+                send(ClientMessage::Resume { token });
+            } else {
+                send(ClientMessage::Connect {
+                    nickname: get_input_value("nickname"),
+                });
+            }
+            self.has_tested = true;
+        }
+    }
+
+    pub fn on_connection_lost(&mut self) -> bool {
+        // called by setup.js when the websocket drops. if we have a resume token, forget the board (the server resends
+        // all of it when we resume) and tell setup.js to reconnect
+        if self.resume_token.is_none() {
+            return false;
+        }
+        self.has_tested = false;
+        self.object_data.clear();
+        self.territory_data.clear();
+        self.fabber_data.clear();
+        self.player_data.clear();
+        self.lasers.clear();
+        self.explosions.clear();
+        self.gun_states.clear();
+        self.snapshots.clear();
+        self.reported_view = None;
+        self.active_piece = None;
+        self.hovered = None;
+        self.hovered_anything = None;
+        self.updating_node = None;
+        true
+    }
+
+    pub fn on_chat_submit(&mut self, text: String, channel: &str) {
+        // "/mute name" and "/unmute name" hide (or bring back) someone's chat. only we stop seeing it
+        if let Some((command, name)) = text.trim().split_once(' ') {
+            if command == "/mute" || command == "/unmute" {
+                let name = name.trim();
+                let Some(player) = self.player_data.values().find(|p| p.name == name) else {
+                    add_chat_line("all", "server", &format!("there's nobody called {}", name));
+                    return;
+                };
+                if player.id == self.id {
                     return;
                 }
+                if command == "/mute" {
+                    self.muted.insert(player.id);
+                    add_chat_line("all", "server", &format!("muted {}", name));
+                } else {
+                    self.muted.remove(&player.id);
+                    add_chat_line("all", "server", &format!("unmuted {}", name));
+                }
+                return;
             }
-            alert(&format!("server failed verification: {:?}", message));
+        }
+        let channel = match channel {
+            "team" => ChatChannel::Team,
+            "spectators" => ChatChannel::Spectators,
+            _ => ChatChannel::All,
+        };
+        if !self.features.contains(Features::CHAT) {
+            add_chat_line("all", "server", "chat is turned off on this server");
+        } else if !text.trim().is_empty() {
+            send(ClientMessage::Chat { channel, text });
         }
     }
 
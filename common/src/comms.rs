@@ -14,6 +14,7 @@
 // includes Server -> Client and Client -> Server
 // impls where applicable sorted to the bottom of the file
 
+use crate::fab::{FabLevels, TechLine};
 use crate::pathfollower::PathNode;
 use crate::types::PieceType;
 use crate::PieceId;
@@ -28,18 +29,93 @@ pub enum StrategyPathModification {
     Delete(PieceId, u16),
 }
 
+impl StrategyPathModification {
+    pub fn piece(&self) -> PieceId {
+        match self {
+            Self::Insert(piece, ..)
+            | Self::Clear(piece)
+            | Self::Set(piece, ..)
+            | Self::Delete(piece, ..) => *piece,
+        }
+    }
+}
+
 #[derive(Debug, Encode, Decode, PartialEq)]
 pub enum ObjectSpecialPropertySet {
     GunState(bool),
+    Construct, // constructor ships: stop here and start building
+    Repair, // pay to fix the piece up completely. has to be inside a friendly fabber that can build it
+}
+
+/// Why the server turned down a command. Sent back in CommandRejected with the request id the command came with.
+#[derive(Copy, Clone, Encode, Decode, Debug, PartialEq)]
+pub enum RejectionReason {
+    WrongStage, // not right now: castles go down before the game starts, everything else during strategy
+    CastleAlreadyPlaced, // one castle each
+    TooCloseToTerritory, // a castle has to be clear of everyone else's territory
+    NotPlaceable, // players can't place this type of piece
+    InsufficientFunds,
+    OutsideFabber, // not within range of a fabber (of ours or our team's) that can build this
+    NoSuchPiece,   // it's gone, or it never existed
+    NotYours,
+    NotMovable,      // the piece doesn't follow paths
+    NoGun,           // the piece doesn't have a gun to turn on or off
+    NotAConstructor, // the piece can't build anything
+    NotAFabber,      // the piece doesn't have a fabber bay to upgrade
+    MaxLevel,        // that tech line is as high as it goes
+    NotDamaged,      // nothing to repair
+}
+
+impl RejectionReason {
+    pub fn get_str(self) -> &'static str {
+        match self {
+            Self::WrongStage => "you can't do that right now",
+            Self::CastleAlreadyPlaced => "you already have a castle",
+            Self::TooCloseToTerritory => "too close to someone else's territory",
+            Self::NotPlaceable => "that can't be placed",
+            Self::InsufficientFunds => "not enough money",
+            Self::OutsideFabber => "outside the range of your fabbers",
+            Self::NoSuchPiece => "that piece doesn't exist",
+            Self::NotYours => "that piece isn't yours",
+            Self::NotMovable => "that piece can't move",
+            Self::NoGun => "that piece doesn't have a gun",
+            Self::NotAConstructor => "that piece can't build anything",
+            Self::NotAFabber => "that piece doesn't have a fabber",
+            Self::MaxLevel => "that fabber can't go any higher",
+            Self::NotDamaged => "that piece isn't damaged",
+        }
+    }
+}
+
+/// Who a chat message is for.
+#[derive(Copy, Clone, Encode, Decode, Debug, PartialEq)]
+pub enum ChatChannel {
+    All,        // everybody on the server, spectators included
+    Team,       // players in the sender's team slot. free agents don't have a team to talk to
+    Spectators, // spectators only. this is the only channel spectators may send on
+}
+
+impl ChatChannel {
+    pub fn get_str(self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::Team => "team",
+            Self::Spectators => "spectators",
+        }
+    }
 }
 
 #[derive(Debug, Encode, Decode, PartialEq)]
 pub enum ClientMessage {
     // client -> server
-    Test(String, u8, u16, u32, u64, i8, i16, i32, i64, f32, f64, u8), // the test message. See ServerMessage.
+    Hello {
+        magic: String,
+        protocol: ProtocolVersion,
+        features: Features,
+    }, // the answer to the server's Hello: which protocol we speak and which of the server's features we want
     Connect {
         nickname: String,
-    },                          // connect to the server. doesn't let you place your castle yet.
+    }, // connect to the server. doesn't let you place your castle yet.
     // if this works immediately, the server will send Metadata(...), which signals the user to begin playing.
     // the server can also send a challenge: PasswordChallenge or TeamChallenge. In the case of PasswordChallenge,
     // the client will send TryPassword. In the case of TeamChallenge, the client will send TryTeam. In either case,
@@ -53,28 +129,117 @@ pub enum ClientMessage {
     },
     TrySpectate, // I don't want to play, I just want to watch
     PlacePiece {
+        request_id: u32,
         x: f32,
         y: f32,
         tp: PieceType,
     }, // x, y, type
-    // attempt to place an object
+    // attempt to place an object. request_id is whatever the client likes; if the placement is refused, the server sends it
+    // back in CommandRejected. the same goes for Strategy and Special
     // before the client can place anything else, it must place a castle (type 1). this is the only time in the game that a client can place an object in neutral territory.
     // obviously it's not possible to place a castle in enemy territory
     Strategy {
+        request_id: u32,
         evt: StrategyPathModification,
     },
     Special {
+        request_id: u32,
         id: PieceId,
         evt: ObjectSpecialPropertySet,
     },
+    // buy the next level of one tech line on one of our fabbers. costs TechLine::price_per_level times the new level
+    UpgradeFabber {
+        request_id: u32,
+        id: PieceId,
+        line: TechLine,
+    },
+    // sent instead of Connect after a dropped connection: pick up the player (and pieces) this token belongs to.
+    // the server answers with a full resync starting at Metadata, or ResumeFailed if the token is unknown or expired.
+    Resume {
+        token: u128,
+    },
+    Chat {
+        channel: ChatChannel,
+        text: String,
+    }, // say something. the server may truncate it, or drop it if we're talking too much
+    SnapshotAck {
+        sequence: u32,
+    }, // we've applied this Snapshot. the server sends future snapshots as deltas against it
+    Viewport {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    }, // the part of the board we're looking at (x, y is the top-left corner). the server only sends us what's near it
 }
 
-// upon connecting, the server immediately sends the client Test("EXOSPHERE", 128, 4096, 115600, 123456789012345, -64, -4096, -115600, -123456789012345, -4096.512, -8192.756, VERSION)
-// if the client can successfully decode the message, and VERSION matches the client version, the game may proceed. Otherwise, the client will immediately drop the connection.
-// to signify that the test passed, the client will send a Test() with the same values and the client version. If the server can successfully decode them, and the version the
-// client sends matches VERSION, the game may proceed. Otherwise, the server will abort the connection.
-// this exchange prevents old, underprepared, or incompatible clients from connecting to a game.
-// If a client attempts to do anything before protocol verification, it will be kicked off the server.
+// upon connecting, the server immediately sends the client Hello { magic: "EXOSPHERE", protocol: PROTOCOL, features } listing
+// the optional features it offers. if the magic is wrong or the major version doesn't match its own, the client gives up.
+// otherwise it answers with its own Hello: the protocol version it speaks and the features it wants, out of the ones offered.
+// the server accepts any client with the same major version and the same or an older minor version, and from then on
+// both ends use the features they have in common; anything else gets the connection closed with a reason saying why.
+// If a client attempts to do anything before its Hello, it will be kicked off the server.
+
+/// A protocol version. Different major versions can't talk to each other at all. Messages are bitcode-encoded, which
+/// packs an enum's tag according to how many variants it has and has no way of skipping fields it doesn't know, so
+/// any change to an encoded type (a new message, a new piece type, a new field) breaks the wire format and needs a
+/// new major version. A minor version only changes things that leave the encoding alone, like what the server does
+/// with a message, so a server can serve every older minor version of its own major version.
+#[derive(Copy, Clone, Encode, Decode, Debug, PartialEq, Eq)]
+pub struct ProtocolVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl std::fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// A set of optional protocol features. The server offers some in its Hello, the client asks for some in its Hello,
+/// and the connection gets the ones they both have.
+#[derive(Copy, Clone, Encode, Decode, Debug, PartialEq, Eq, Default)]
+pub struct Features(pub u32);
+
+impl Features {
+    pub const NONE: Features = Features(0);
+    pub const COMPRESSION: Features = Features(1); // compressed frames. reserved: no server offers this yet
+    pub const SNAPSHOTS: Features = Features(2); // delta snapshots: without this, every snapshot is sent in full and acks are ignored
+    pub const CHAT: Features = Features(4); // chat messages, both ways
+    pub const RESUME: Features = Features(8); // resume tokens, so a dropped client can get its pieces back
+
+    pub fn contains(self, other: Features) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn names(self) -> Vec<&'static str> {
+        [
+            (Self::COMPRESSION, "compression"),
+            (Self::SNAPSHOTS, "snapshots"),
+            (Self::CHAT, "chat"),
+            (Self::RESUME, "resume"),
+        ]
+        .into_iter()
+        .filter(|(feature, _)| self.contains(*feature))
+        .map(|(_, name)| name)
+        .collect()
+    }
+}
+
+impl std::ops::BitOr for Features {
+    type Output = Features;
+    fn bitor(self, other: Features) -> Features {
+        Features(self.0 | other.0)
+    }
+}
+
+impl std::ops::BitAnd for Features {
+    type Output = Features;
+    fn bitand(self, other: Features) -> Features {
+        Features(self.0 & other.0)
+    }
+}
 
 #[derive(Copy, Clone, Encode, Decode, Debug, PartialEq)]
 pub enum Stage {
@@ -93,12 +258,124 @@ impl Stage {
     }
 }
 
+/// Why the server is disconnecting a client. This is sent in ServerMessage::Disconnect, and the same
+/// text goes out as the reason in the websocket close frame.
+#[derive(Copy, Clone, Encode, Decode, Debug, PartialEq)]
+pub enum DisconnectReason {
+    GameOver,
+    ServerFull,
+    KickedByAdmin,
+    HandshakeFailed,
+    IncompatibleProtocol,
+}
+
+impl DisconnectReason {
+    pub fn get_str(self) -> &'static str {
+        match self {
+            Self::GameOver => "game over",
+            Self::ServerFull => "server full",
+            Self::KickedByAdmin => "kicked by admin",
+            Self::HandshakeFailed => "handshake failed",
+            Self::IncompatibleProtocol => "incompatible protocol version",
+        }
+    }
+}
+
+/// One piece's position in a Snapshot, quantized: x and y are fractions of the board's width and height in
+/// 65535ths (pieces off the edge of the board are clamped to it), and the angle is in 65536ths of a turn.
+/// Velocity is in 8ths of a unit per second and angular velocity in 1024ths of a radian per second, so the client can
+/// keep the piece moving between samples. `age` is how many ticks before the snapshot this was sampled: the server
+/// only samples a piece again once it strays from where this says it should be.
+#[derive(Copy, Clone, Encode, Decode, Debug, PartialEq)]
+pub struct SnapshotMove {
+    pub id: PieceId,
+    pub x: u16,
+    pub y: u16,
+    pub a: u16,
+    pub vx: i16,
+    pub vy: i16,
+    pub va: i16,
+    pub age: u8,
+}
+
+impl SnapshotMove {
+    pub fn new(id: PieceId, x: f32, y: f32, a: f32, board_width: f32, board_height: f32) -> Self {
+        let turn = std::f32::consts::TAU;
+        Self {
+            id,
+            x: (x / board_width).clamp(0.0, 1.0).mul_add(65535.0, 0.5) as u16,
+            y: (y / board_height).clamp(0.0, 1.0).mul_add(65535.0, 0.5) as u16,
+            a: ((a.rem_euclid(turn) / turn * 65536.0).round() as u32 & 0xffff) as u16,
+            vx: 0,
+            vy: 0,
+            va: 0,
+            age: 0,
+        }
+    }
+
+    pub fn with_velocity(self, vx: f32, vy: f32, va: f32) -> Self {
+        let quantize = |v: f32| v.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        Self {
+            vx: quantize(vx * 8.0),
+            vy: quantize(vy * 8.0),
+            va: quantize(va * 1024.0),
+            ..self
+        }
+    }
+
+    pub fn x(&self, board_width: f32) -> f32 {
+        self.x as f32 / 65535.0 * board_width
+    }
+
+    pub fn y(&self, board_height: f32) -> f32 {
+        self.y as f32 / 65535.0 * board_height
+    }
+
+    pub fn a(&self) -> f32 {
+        self.a as f32 / 65536.0 * std::f32::consts::TAU
+    }
+
+    pub fn vx(&self) -> f32 {
+        self.vx as f32 / 8.0
+    }
+
+    pub fn vy(&self) -> f32 {
+        self.vy as f32 / 8.0
+    }
+
+    pub fn va(&self) -> f32 {
+        self.va as f32 / 1024.0
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.vx != 0 || self.vy != 0 || self.va != 0
+    }
+
+    // where the piece should be this long after it was sampled, if it kept going the way it was
+    pub fn extrapolate(
+        &self,
+        seconds: f32,
+        board_width: f32,
+        board_height: f32,
+    ) -> (f32, f32, f32) {
+        (
+            self.x(board_width) + self.vx() * seconds,
+            self.y(board_height) + self.vy() * seconds,
+            self.a() + self.va() * seconds,
+        )
+    }
+}
+
 /// ServerMessage represents a data frame sent from the Bevy gameserver to the websocket client.
 #[derive(Debug, Encode, Decode, Clone)]
 pub enum ServerMessage {
-    /// The standard test frame. This is sent to the client on startup, and an exactly equal one is sent back;
-    /// if ever they don't match, the connection will not proceed.
-    Test(String, u8, u16, u32, u64, i8, i16, i32, i64, f32, f64, u8),
+    /// The first thing sent to a new client: the protocol version the server speaks and the optional features
+    /// it offers. The client answers with its own Hello.
+    Hello {
+        magic: String,
+        protocol: ProtocolVersion,
+        features: Features,
+    },
     GameState {
         stage: Stage,
         stage_duration: u16,
@@ -111,6 +388,8 @@ pub enum ServerMessage {
         slot: u8,
         board_width: f32,
         board_height: f32,
+        resume_token: u128, // hang onto this: it's how the client gets its pieces back if the connection drops
+        fog_of_war: bool, // enemies are only sent while they're in sight of one of our (or our team's) pieces
     },
     /// A password is required on this server. The client should send a password attempt
     /// or request to spectate.
@@ -132,8 +411,16 @@ pub enum ServerMessage {
         owner: PlayerId,
         tp: PieceType,
     },
-    /// A piece on the gameboard was moved.
-    ObjectMove { id: PieceId, x: f32, y: f32, a: f32 },
+    /// Where the pieces on the board are, as a delta against the snapshot numbered `baseline`, which is the last one
+    /// we acknowledged: `moves` has every piece that's been sampled since then and `removed` every piece that's gone. Pieces
+    /// that aren't mentioned are where they were in the baseline. A baseline of 0 means no baseline, so every piece is in
+    /// `moves`. The client should ack each snapshot it applies.
+    Snapshot {
+        sequence: u32,
+        baseline: u32,
+        moves: Vec<SnapshotMove>,
+        removed: Vec<PieceId>,
+    },
     /// A piece on the gameboard was deleted.
     DeleteObject { id: PieceId },
     /// A node in a strategy path has been fulfilled: this tells the client
@@ -154,9 +441,30 @@ pub enum ServerMessage {
     /// Establish a territory influence around an object.
     Territory { id: PieceId, radius: f32 },
     /// Establish a fabber influence around an object.
-    Fabber { id: PieceId, radius: f32 },
-    /// The client will be disconnected.
-    Disconnect,
+    /// Sent again whenever the fabber is upgraded.
+    Fabber {
+        id: PieceId,
+        radius: f32,
+        levels: FabLevels,
+    },
+    /// The client will be disconnected, and this is why. The server follows it up with a websocket
+    /// close frame carrying the same reason.
+    Disconnect { reason: DisconnectReason },
+    /// Somebody said something on a chat channel we can hear. `from` is SYSTEM for notices from the
+    /// server itself (like being told to slow down).
+    ChatMessage {
+        from: PlayerId,
+        channel: ChatChannel,
+        text: String,
+    },
+    /// The server refused to carry out a PlacePiece, Strategy or Special command. `request_id` is the one the
+    /// command was sent with.
+    CommandRejected {
+        request_id: u32,
+        reason: RejectionReason,
+    },
+    /// The client tried to Resume with a token that's unknown or has expired. It should Connect as a new player.
+    ResumeFailed,
     /// The client has this amount of cash on hand!
     Money { id: PlayerId, amount: u32 },
     /// Something blew up. The explosion should be cleared
@@ -179,3 +487,27 @@ pub enum ServerMessage {
         to_y: f32,
     },
 }
+
+impl ClientMessage {
+    /// The feature a client needs to have been granted to send this. Messages for features a connection
+    /// doesn't have are dropped.
+    pub fn requires(&self) -> Features {
+        match self {
+            Self::SnapshotAck { .. } => Features::SNAPSHOTS,
+            Self::Chat { .. } => Features::CHAT,
+            Self::Resume { .. } => Features::RESUME,
+            _ => Features::NONE,
+        }
+    }
+}
+
+impl ServerMessage {
+    /// The feature a client needs to have been granted to be sent this. Clients without it never see it.
+    pub fn requires(&self) -> Features {
+        match self {
+            Self::ChatMessage { .. } => Features::CHAT,
+            Self::ResumeFailed => Features::RESUME,
+            _ => Features::NONE,
+        }
+    }
+}
@@ -1,6 +1,8 @@
 // global structures about fabbers
 
-#[derive(PartialEq)]
+use bitcode::{ Encode, Decode };
+
+#[derive(PartialEq, Clone, Copy, Debug, Encode, Decode)]
 pub struct FabLevels {
     pub missiles : u8,
     pub ships : u8,
@@ -65,6 +67,60 @@ impl FabLevels {
     pub fn buildings(lev : u8) -> Self {
         Self::default().with_buildings(lev)
     }
+
+    pub fn get(&self, line : TechLine) -> u8 {
+        match line {
+            TechLine::Missiles => self.missiles,
+            TechLine::Ships => self.ships,
+            TechLine::Econ => self.econ,
+            TechLine::Defense => self.defense,
+            TechLine::Buildings => self.buildings
+        }
+    }
+
+    pub fn upgrade(&mut self, line : TechLine) {
+        match line {
+            TechLine::Missiles => self.missiles += 1,
+            TechLine::Ships => self.ships += 1,
+            TechLine::Econ => self.econ += 1,
+            TechLine::Defense => self.defense += 1,
+            TechLine::Buildings => self.buildings += 1
+        }
+    }
+}
+
+
+impl std::fmt::Display for FabLevels {
+    fn fmt(&self, f : &mut std::fmt::Formatter) -> std::fmt::Result {
+        // same shorthand as the techtree, minus the size: M4S2E2D3B2
+        write!(f, "M{}S{}E{}D{}B{}", self.missiles, self.ships, self.econ, self.defense, self.buildings)
+    }
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Encode, Decode)]
+pub enum TechLine {
+    Missiles,
+    Ships,
+    Econ,
+    Defense,
+    Buildings
+}
+
+
+impl TechLine {
+    pub const MAX_LEVEL : u8 = 5; // named tech lives at level 5; nothing goes higher
+
+    pub fn price_per_level(&self) -> u32 {
+        // upgrading to level n costs n times this
+        match self {
+            Self::Missiles => 700,
+            Self::Ships => 600,
+            Self::Econ => 50,
+            Self::Defense => 200,
+            Self::Buildings => 800
+        }
+    }
 }
 
 
@@ -11,7 +11,9 @@
 */
 
 pub mod comms;
-pub const VERSION: u8 = 2; // bump this up every time a major change is made (overflow at 256; this is not meant to be an authoritative correct version)
+pub const PROTOCOL: comms::ProtocolVersion = comms::ProtocolVersion { major: 7, minor: 0 }; // bump major for any change to the encoded types in comms (see ProtocolVersion), minor for anything else
+pub const MAGIC: &str = "EXOSPHERE"; // starts off every Hello, so we know we're talking to exosphere at all
+pub const UPDATE_RATE: u64 = 30; // server ticks per second. snapshots are numbered in ticks, so the client needs this too
 pub mod fab;
 pub mod pathfollower;
 mod steal_mut;
@@ -52,6 +54,14 @@ impl std::convert::From<bevy::prelude::Entity> for PieceId {
     }
 }
 
+#[cfg(feature = "server")]
+impl PieceId {
+    // for ids that came from a client: they might not be entities at all, and converting those with into() panics
+    pub fn entity(self) -> Option<bevy::prelude::Entity> {
+        bevy::prelude::Entity::try_from_bits(self.0).ok()
+    }
+}
+
 #[cfg(feature = "server")]
 impl std::convert::Into<bevy::prelude::Entity> for PieceId {
     fn into(self) -> bevy::prelude::Entity {
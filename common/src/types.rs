@@ -38,31 +38,39 @@ use num_derive::FromPrimitive;
 #[repr(u16)]
 #[derive(Copy, Clone, Debug, PartialEq, FromPrimitive, Encode, Decode)]
 pub enum PieceType {
-    BasicFighter,      // impl
-    Castle,            // impl
-    Bullet,            // impl
-    TieFighter,        // impl
-    Sniper,            // impl
-    DemolitionCruiser, // impl
-    Battleship,        // impl
-    SmallBomb,         // impl
-    Seed,              // impl
-    Chest,             // impl
-    Farmhouse,         // impl
-    BallisticMissile,  // impl
-    FleetDefenseShip,  // todo
-    SeekingMissile,    // impl
-    HypersonicMissile, // impl
-    TrackingMissile,   // impl
-    CruiseMissile,     // impl
-    ScrapShip,         // impl
-    LaserNode,         // impl
-    BasicTurret,       // impl
-    LaserNodeLR,       // impl
-    SmartTurret,       // impl
-    BlastTurret,       // todo
-    LaserTurret,       // todo
-    EmpZone,           // todo
+    BasicFighter,          // impl
+    Castle,                // impl
+    Bullet,                // impl
+    TieFighter,            // impl
+    Sniper,                // impl
+    DemolitionCruiser,     // impl
+    Battleship,            // impl
+    SmallBomb,             // impl
+    Seed,                  // impl
+    Chest,                 // impl
+    Farmhouse,             // impl
+    BallisticMissile,      // impl
+    FleetDefenseShip,      // impl
+    SeekingMissile,        // impl
+    HypersonicMissile,     // impl
+    TrackingMissile,       // impl
+    CruiseMissile,         // impl
+    ScrapShip,             // impl
+    LaserNode,             // impl
+    BasicTurret,           // impl
+    LaserNodeLR,           // impl
+    SmartTurret,           // impl
+    BlastTurret,           // impl
+    LaserTurret,           // impl
+    EmpZone,               // impl
+    OutpostConstructor,    // impl
+    FortConstructor,       // impl
+    CastleConstructor,     // impl
+    Outpost,               // impl
+    Fort,                  // impl
+    TeleportalConstructor, // impl
+    Teleportal,            // impl
+    Madecs,                // impl
 }
 
 pub enum Asset {
@@ -136,11 +144,19 @@ impl PieceType {
             Self::HypersonicMissile => 20,
             Self::TrackingMissile => 30,
             Self::CruiseMissile => 50,
+            Self::Madecs => 700,
             Self::LaserNode => 10,
             Self::ScrapShip => 20,
             Self::LaserNodeLR => 80,
             Self::BasicTurret => 50,
             Self::SmartTurret => 100,
+            Self::BlastTurret => 150,
+            Self::LaserTurret => 250,
+            Self::EmpZone => 400,
+            Self::OutpostConstructor => 300,
+            Self::TeleportalConstructor => 600,
+            Self::FortConstructor => 800,
+            Self::CastleConstructor => 3000,
             _ => 0,
         }
     }
@@ -151,6 +167,7 @@ impl PieceType {
             | Self::TieFighter
             | Self::Sniper
             | Self::CruiseMissile
+            | Self::Madecs
             | Self::DemolitionCruiser
             | Self::Battleship
             | Self::Seed
@@ -164,7 +181,14 @@ impl PieceType {
             | Self::FleetDefenseShip
             | Self::LaserNodeLR
             | Self::BasicTurret
-            | Self::SmartTurret => true, // if you want a type to be user placeable, just add it to this lil' blob.
+            | Self::SmartTurret
+            | Self::BlastTurret
+            | Self::LaserTurret
+            | Self::EmpZone
+            | Self::OutpostConstructor
+            | Self::FortConstructor
+            | Self::CastleConstructor
+            | Self::TeleportalConstructor => true, // if you want a type to be user placeable, just add it to this lil' blob.
             _ => false,
         }
     }
@@ -175,6 +199,7 @@ impl PieceType {
             | Self::TieFighter
             | Self::Sniper
             | Self::CruiseMissile
+            | Self::Madecs
             | Self::DemolitionCruiser
             | Self::Battleship
             | Self::Seed
@@ -183,7 +208,11 @@ impl PieceType {
             | Self::SeekingMissile
             | Self::HypersonicMissile
             | Self::ScrapShip
-            | Self::FleetDefenseShip => true, // if you want a type to be movable, just add it to this lil' blob.
+            | Self::FleetDefenseShip
+            | Self::OutpostConstructor
+            | Self::FortConstructor
+            | Self::CastleConstructor
+            | Self::TeleportalConstructor => true, // if you want a type to be movable, just add it to this lil' blob.
             _ => false,
         }
     }
@@ -193,6 +222,7 @@ impl PieceType {
             Self::BasicFighter => FabLevels::ships(1),
             Self::TieFighter => FabLevels::ships(1),
             Self::Sniper => FabLevels::ships(1),
+            Self::FleetDefenseShip => FabLevels::ships(2),
             Self::DemolitionCruiser => FabLevels::ships(2),
             Self::Battleship => FabLevels::ships(3),
             Self::Seed => FabLevels::econ(1),
@@ -202,11 +232,19 @@ impl PieceType {
             Self::HypersonicMissile => FabLevels::missiles(2),
             Self::TrackingMissile => FabLevels::missiles(3),
             Self::CruiseMissile => FabLevels::missiles(4),
+            Self::Madecs => FabLevels::missiles(5),
             Self::LaserNode => FabLevels::defense(1),
             Self::ScrapShip => FabLevels::econ(2),
             Self::LaserNodeLR => FabLevels::defense(2),
             Self::BasicTurret => FabLevels::defense(1),
             Self::SmartTurret => FabLevels::defense(2),
+            Self::BlastTurret => FabLevels::defense(3),
+            Self::LaserTurret => FabLevels::defense(3),
+            Self::EmpZone => FabLevels::defense(4),
+            Self::OutpostConstructor => FabLevels::buildings(1),
+            Self::TeleportalConstructor => FabLevels::buildings(1),
+            Self::FortConstructor => FabLevels::buildings(2),
+            Self::CastleConstructor => FabLevels::buildings(3),
             _ => FabLevels::default(),
         }
     }
@@ -236,7 +274,10 @@ impl PieceType {
                 "ballistic_missile_friendly.svg",
                 "ballistic_missile_enemy.svg",
             ),
-            Self::FleetDefenseShip => Asset::Unimpl,
+            Self::FleetDefenseShip => Asset::Partisan(
+                "fleet_defense_ship_friendly.svg",
+                "fleet_defense_ship_enemy.svg",
+            ),
             Self::SeekingMissile => {
                 Asset::Partisan("seeking_missile_friendly.svg", "seeking_missile_enemy.svg")
             }
@@ -251,6 +292,7 @@ impl PieceType {
             Self::CruiseMissile => {
                 Asset::Partisan("cruise_missile_friendly.svg", "cruise_missile_enemy.svg")
             }
+            Self::Madecs => Asset::Partisan("madecs_friendly.svg", "madecs_enemy.svg"),
             Self::LaserNode => Asset::Simple("lasernode.svg"),
             Self::ScrapShip => Asset::Simple("scrapship.svg"),
             Self::LaserNodeLR => Asset::Simple("lasernode_lr.svg"),
@@ -260,7 +302,32 @@ impl PieceType {
             Self::SmartTurret => {
                 Asset::Partisan("smart_turret_friendly.svg", "smart_turret_enemy.svg")
             }
-            _ => Asset::Unimpl,
+            Self::BlastTurret => {
+                Asset::Partisan("blast_turret_friendly.svg", "blast_turret_enemy.svg")
+            }
+            Self::LaserTurret => {
+                Asset::Partisan("laser_turret_friendly.svg", "laser_turret_enemy.svg")
+            }
+            Self::EmpZone => Asset::Partisan("emp_zone_friendly.svg", "emp_zone_enemy.svg"),
+            Self::OutpostConstructor => Asset::Partisan(
+                "outpost_constructor_friendly.svg",
+                "outpost_constructor_enemy.svg",
+            ),
+            Self::FortConstructor => Asset::Partisan(
+                "fort_constructor_friendly.svg",
+                "fort_constructor_enemy.svg",
+            ),
+            Self::CastleConstructor => Asset::Partisan(
+                "castle_constructor_friendly.svg",
+                "castle_constructor_enemy.svg",
+            ),
+            Self::Outpost => Asset::Partisan("outpost_friendly.svg", "outpost_enemy.svg"),
+            Self::Fort => Asset::Partisan("fort_friendly.svg", "fort_enemy.svg"),
+            Self::TeleportalConstructor => Asset::Partisan(
+                "teleportal_constructor_friendly.svg",
+                "teleportal_constructor_enemy.svg",
+            ),
+            Self::Teleportal => Asset::Partisan("teleportal_friendly.svg", "teleportal_enemy.svg"),
         }
     }
 
@@ -278,17 +345,27 @@ impl PieceType {
             Self::Chest => Shape::Box(20.0, 20.0),
             Self::Farmhouse => Shape::Box(50.0, 50.0),
             Self::BallisticMissile => Shape::Box(35.0, 20.0),
-            Self::FleetDefenseShip => Shape::Unimpl,
+            Self::FleetDefenseShip => Shape::Box(45.0, 30.0),
             Self::SeekingMissile => Shape::Box(35.0, 20.0),
             Self::HypersonicMissile => Shape::Box(35.0, 10.0),
             Self::TrackingMissile => Shape::Box(35.0, 17.0),
             Self::CruiseMissile => Shape::Box(35.0, 10.0),
+            Self::Madecs => Shape::Box(50.0, 16.0),
             Self::LaserNode => Shape::Box(15.0, 15.0),
             Self::ScrapShip => Shape::Box(50.0, 50.0),
             Self::LaserNodeLR => Shape::Box(30.0, 30.0),
             Self::BasicTurret => Shape::Box(40.0, 25.0),
             Self::SmartTurret => Shape::Box(40.0, 25.0),
-            _ => Shape::Unimpl,
+            Self::BlastTurret => Shape::Box(40.0, 30.0),
+            Self::LaserTurret => Shape::Box(40.0, 25.0),
+            Self::EmpZone => Shape::Box(40.0, 40.0),
+            Self::OutpostConstructor
+            | Self::FortConstructor
+            | Self::CastleConstructor
+            | Self::TeleportalConstructor => Shape::Box(50.0, 35.0),
+            Self::Outpost => Shape::Box(40.0, 40.0),
+            Self::Fort => Shape::Box(50.0, 50.0),
+            Self::Teleportal => Shape::Box(30.0, 30.0),
         }
     }
 
@@ -299,8 +376,13 @@ impl PieceType {
             Self::LaserNode => Some(200.0),
             Self::LaserNodeLR => Some(600.0),
             Self::ScrapShip => Some(300.0),
+            Self::FleetDefenseShip => Some(40.0),
             Self::BasicTurret => Some(350.0),
             Self::SmartTurret => Some(350.0),
+            Self::BlastTurret => Some(500.0),
+            Self::LaserTurret => Some(400.0),
+            Self::EmpZone => Some(400.0),
+            Self::Teleportal => Some(60.0),
             _ => None,
         }
     }
@@ -310,22 +392,83 @@ impl PieceType {
         if let Some(sensor) = self.sensor() {
             return Some(sensor);
         }
-        if let PieceType::Castle = *self {
-            return Some(600.0);
+        match self {
+            PieceType::Castle => return Some(600.0),
+            PieceType::Fort => return Some(400.0),
+            _ => {}
         }
         None
     }
 
+    pub fn vision(&self) -> f32 {
+        // how far this piece can see through the fog of war. anything with a sensor sees at least as far as it reaches
+        let vision = match self {
+            Self::Castle => 1000.0,
+            Self::Fort => 800.0,
+            Self::Outpost => 600.0,
+            Self::Sniper => 800.0,
+            Self::DemolitionCruiser | Self::Battleship | Self::FleetDefenseShip => 600.0,
+            Self::BasicFighter | Self::TieFighter => 500.0,
+            Self::ScrapShip
+            | Self::LaserNode
+            | Self::LaserNodeLR
+            | Self::BasicTurret
+            | Self::SmartTurret
+            | Self::BlastTurret
+            | Self::LaserTurret => 400.0,
+            Self::OutpostConstructor
+            | Self::FortConstructor
+            | Self::CastleConstructor
+            | Self::TeleportalConstructor => 500.0,
+            Self::Farmhouse | Self::EmpZone | Self::Teleportal => 300.0,
+            Self::BallisticMissile
+            | Self::SeekingMissile
+            | Self::HypersonicMissile
+            | Self::TrackingMissile
+            | Self::CruiseMissile
+            | Self::Madecs => 250.0,
+            Self::Bullet | Self::SmallBomb | Self::Seed | Self::Chest => 0.0,
+        };
+        self.sensor().map_or(vision, |sensor| sensor.max(vision))
+    }
+
     pub fn show_field(&self) -> bool {
         // should the field for this piece be visible at all times?
         match self {
             Self::Farmhouse => true, // some types have overrides for fields drawn on GPU, this is just the ones drawn on CPU
             Self::BasicTurret => true,
             Self::SmartTurret => true,
+            Self::BlastTurret => true,
+            Self::LaserTurret => true,
+            Self::EmpZone => true,
+            Self::Teleportal => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_missile(&self) -> bool {
+        match self {
+            Self::BallisticMissile
+            | Self::SeekingMissile
+            | Self::HypersonicMissile
+            | Self::TrackingMissile
+            | Self::CruiseMissile
+            | Self::Madecs => true,
             _ => false,
         }
     }
 
+    pub fn constructs(&self) -> Option<PieceType> {
+        // what a constructor ship turns into once it's done building
+        match self {
+            Self::OutpostConstructor => Some(Self::Outpost),
+            Self::FortConstructor => Some(Self::Fort),
+            Self::CastleConstructor => Some(Self::Castle),
+            Self::TeleportalConstructor => Some(Self::Teleportal),
+            _ => None,
+        }
+    }
+
     pub fn supports_target_control(&self) -> bool {
         match self {
             Self::TrackingMissile => true,
@@ -352,12 +495,22 @@ impl PieceType {
             Self::HypersonicMissile => "Hypersonic Missile",
             Self::TrackingMissile => "Tracking Missile",
             Self::CruiseMissile => "Cruise Missile",
+            Self::Madecs => "MADECS",
             Self::LaserNode => "Small Laser Node",
             Self::ScrapShip => "Scrap Ship",
             Self::LaserNodeLR => "Large Laser Node",
             Self::BasicTurret => "Basic Turret",
             Self::SmartTurret => "Smart Turret",
-            _ => "",
+            Self::BlastTurret => "Blast Turret",
+            Self::LaserTurret => "Laser Turret",
+            Self::EmpZone => "EMP Zone",
+            Self::OutpostConstructor => "Outpost Constructor",
+            Self::FortConstructor => "Fort Constructor",
+            Self::CastleConstructor => "Castle Constructor",
+            Self::Outpost => "Outpost",
+            Self::Fort => "Fort",
+            Self::TeleportalConstructor => "Teleportal Constructor",
+            Self::Teleportal => "Teleportal",
         }
     }
 
@@ -376,17 +529,27 @@ impl PieceType {
             Self::Chest => "Static piece that grants whichever player kills it 20 coins",
             Self::Farmhouse => "Farmhouse that produces a small farm field in which seeds can be grown into chests",
             Self::BallisticMissile => "Cheap kinetic missile",
-            Self::FleetDefenseShip => "unimplemented",
+            Self::FleetDefenseShip => "Medium-speed ship that fires short lasers at enemy missiles and bullets in range",
             Self::SeekingMissile => "Kinetic missile that locks onto the first enemy piece it detects",
             Self::HypersonicMissile => "Fast missile with a low-yield warhead",
             Self::TrackingMissile => "Slow missile with a low-yield warhead that can be manually onto enemy pieces",
             Self::CruiseMissile => "Slow missile with a high-yield warhead",
+            Self::Madecs => "Fast cluster missile that splits into 20 glide bombs spread across every enemy nearby, at the end of its path or as soon as it's hit",
             Self::LaserNode => "Small laser node that creates laser walls to nearby laser nodes",
             Self::ScrapShip => "Slow, weak ship that fires laser at nearby chests to collect the $20",
             Self::LaserNodeLR => "Long range laser mnode that creates laser walls to nearby laser nodes",
             Self::BasicTurret => "Automatically swivelling turret that fires bullets at enemies in range",
             Self::SmartTurret => "Automatically swivelling turret that fires bullets at enemies in range, with much better aim",
-            _ => ""
+            Self::BlastTurret => "Automatically swivelling turret that lobs slow, heavy glide bombs at enemies in range",
+            Self::LaserTurret => "Turret that charges up and fires a high-energy laser into the nearest enemy in range, as long as nothing friendly is in the way",
+            Self::EmpZone => "Projects a large EMF that destroys any bullets entering it and slows enemy ships, but doesn't stop lasers",
+            Self::OutpostConstructor => "Slow ship that builds an outpost (a small fabber bay) wherever you tell it to with C",
+            Self::FortConstructor => "Slow ship that builds a fort (a medium fabber bay with some territory) wherever you tell it to with C",
+            Self::CastleConstructor => "Slow ship that builds a whole new castle wherever you tell it to with C",
+            Self::Outpost => "Small fabber bay. Doesn't hold any territory",
+            Self::Fort => "Medium fabber bay that holds a bit of territory",
+            Self::TeleportalConstructor => "Slow ship that builds a pair of teleportals: press C once at each end. Each end takes half as long as other constructors",
+            Self::Teleportal => "Sends any ship that flies into it, yours or anyone else's, out the other end of the pair",
         }
     }
 }
@@ -0,0 +1,274 @@
+/*
+    Copyright 2024 Tyler Clarke.
+
+    This file is part of Exosphere.
+
+    Exosphere is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+    Exosphere is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License along with Exosphere. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// a scripted 10-player battle, for measuring how much the server sends each client.
+// start a server with the battle config, then point this at it:
+//     cargo run --release -- config-battletest.json
+//     cargo run --release --example battle_bandwidth -- [ADDRESS] [SECONDS]
+// every bot places its castle on a ring around the middle of the board, sends all its ships at the middle with their guns
+// on, and counts everything it receives once the shooting starts. the bots all watch the middle of the board, so they
+// only hear about the rest of it when it's theirs.
+//
+// the README's figures come from two 20-second runs of this on release builds, against the server (and with this
+// example) as it was when each change went in. the server from before snapshots predates Hello, Viewport and
+// SnapshotAck, so for it the bots did its old Test handshake instead and sent neither of the others. per client:
+//     one ObjectMove per piece:  81.4 KiB/s in 3525 messages/s, 80.9 KiB/s in 3502
+//     snapshots:                 47.0 KiB/s in 196 messages/s,  47.6 KiB/s in 198
+//     area of interest:          19.8 KiB/s in 125 messages/s,  17.0 KiB/s in 113
+//     velocities (current):      23.0 KiB/s in 165 messages/s,  20.9 KiB/s in 154
+
+use common::comms::*;
+use common::pathfollower::PathNode;
+use common::types::PieceType;
+use common::{PieceId, PlayerId, MAGIC, PROTOCOL};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+const PLAYERS: usize = 10;
+
+struct Bot {
+    socket: TcpStream,
+}
+
+impl Bot {
+    fn connect(address: &str) -> std::io::Result<Self> {
+        let mut socket = TcpStream::connect(address)?;
+        socket.write_all(
+            format!(
+                "GET /game HTTP/1.1\r\nHost: {}\r\nConnection: keep-alive, Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+                address
+            )
+            .as_bytes(),
+        )?;
+        // skip the 101 response
+        let mut response = vec![];
+        let mut byte = [0u8];
+        while !response.ends_with(b"\r\n\r\n") {
+            socket.read_exact(&mut byte)?;
+            response.push(byte[0]);
+        }
+        Ok(Self { socket })
+    }
+
+    fn send(&mut self, message: ClientMessage) -> std::io::Result<()> {
+        let payload = bitcode::encode(&message);
+        let mut frame = vec![0x82];
+        if payload.len() < 126 {
+            frame.push(0x80 | payload.len() as u8);
+        } else {
+            frame.push(0x80 | 126);
+            frame.extend((payload.len() as u16).to_be_bytes());
+        }
+        frame.extend([0; 4]); // an all-zero mask leaves the payload as it is
+        frame.extend(payload);
+        self.socket.write_all(&frame)
+    }
+
+    // the next data frame, and how many bytes it took on the wire
+    fn receive(&mut self) -> std::io::Result<(ServerMessage, usize)> {
+        loop {
+            let mut header = [0u8; 2];
+            self.socket.read_exact(&mut header)?;
+            let mut wire = 2;
+            let length = match header[1] & 127 {
+                126 => {
+                    let mut length = [0u8; 2];
+                    self.socket.read_exact(&mut length)?;
+                    wire += 2;
+                    u16::from_be_bytes(length) as usize
+                }
+                127 => {
+                    let mut length = [0u8; 8];
+                    self.socket.read_exact(&mut length)?;
+                    wire += 8;
+                    u64::from_be_bytes(length) as usize
+                }
+                length => length as usize,
+            };
+            let mut payload = vec![0; length];
+            self.socket.read_exact(&mut payload)?;
+            wire += length;
+            match header[0] & 15 {
+                2 => {
+                    let message = bitcode::decode(&payload)
+                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                    return Ok((message, wire));
+                }
+                8 => return Err(std::io::ErrorKind::ConnectionAborted.into()),
+                9 => {
+                    // answer pings so the heartbeat doesn't reap us
+                    let mut pong = vec![0x8a, 0x80 | length as u8, 0, 0, 0, 0];
+                    pong.extend(payload);
+                    self.socket.write_all(&pong)?;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Tally {
+    bytes: usize,
+    messages: usize,
+    by_kind: HashMap<String, (usize, usize)>, // message kind -> (count, bytes)
+}
+
+fn kind(message: &ServerMessage) -> String {
+    let debug = format!("{:?}", message);
+    debug
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn play(address: &str, number: usize, seconds: u64) -> std::io::Result<Tally> {
+    let mut bot = Bot::connect(address)?;
+    let mut me = PlayerId::SYSTEM;
+    let mut ships: Vec<PieceId> = vec![];
+    let mut center = (0.0, 0.0);
+    let mut ordered = false;
+    let mut started: Option<Instant> = None;
+    let mut tally = Tally::default();
+    loop {
+        let (message, wire) = bot.receive()?;
+        if let Some(started) = started {
+            if started.elapsed() >= Duration::from_secs(seconds) {
+                return Ok(tally);
+            }
+            tally.bytes += wire;
+            tally.messages += 1;
+            let entry = tally.by_kind.entry(kind(&message)).or_default();
+            entry.0 += 1;
+            entry.1 += wire;
+        }
+        match message {
+            ServerMessage::Hello { features, .. } => {
+                // the bots ack snapshots like the real client, and don't need anything else
+                bot.send(ClientMessage::Hello {
+                    magic: MAGIC.to_string(),
+                    protocol: PROTOCOL,
+                    features: features & Features::SNAPSHOTS,
+                })?;
+                bot.send(ClientMessage::Connect {
+                    nickname: format!("bot {}", number),
+                })?;
+            }
+            ServerMessage::Metadata {
+                id,
+                board_width,
+                board_height,
+                ..
+            } => {
+                me = id;
+                center = (board_width / 2.0, board_height / 2.0);
+                // castles on a ring, far enough apart that their territories don't collide
+                let angle = number as f32 / PLAYERS as f32 * std::f32::consts::TAU;
+                let radius = board_width.min(board_height) / 3.0;
+                bot.send(ClientMessage::PlacePiece {
+                    request_id: 0,
+                    x: center.0 + radius * angle.cos(),
+                    y: center.1 + radius * angle.sin(),
+                    tp: PieceType::Castle,
+                })?;
+                // everyone watches the fight in the middle, on a 1080p screen
+                bot.send(ClientMessage::Viewport {
+                    x: center.0 - 960.0,
+                    y: center.1 - 540.0,
+                    width: 1920.0,
+                    height: 1080.0,
+                })?;
+            }
+            ServerMessage::ObjectCreate { id, owner, tp, .. }
+                if owner == me && tp.user_movable() =>
+            {
+                ships.push(id);
+            }
+            ServerMessage::GameState { stage, .. } => {
+                if stage == Stage::MoveShips && !ordered {
+                    ordered = true;
+                    for ship in &ships {
+                        bot.send(ClientMessage::Strategy {
+                            request_id: 0,
+                            evt: StrategyPathModification::Insert(
+                                *ship,
+                                0,
+                                PathNode::StraightTo(center.0, center.1),
+                            ),
+                        })?;
+                        bot.send(ClientMessage::Special {
+                            request_id: 0,
+                            id: *ship,
+                            evt: ObjectSpecialPropertySet::GunState(true),
+                        })?;
+                    }
+                }
+                if stage == Stage::Playing && ordered && started.is_none() {
+                    started = Some(Instant::now());
+                }
+            }
+            ServerMessage::Snapshot { sequence, .. } => {
+                bot.send(ClientMessage::SnapshotAck { sequence })?;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let address = args.next().unwrap_or("127.0.0.1:3000".to_string());
+    let seconds: u64 = args.next().and_then(|s| s.parse().ok()).unwrap_or(20);
+    let bots: Vec<_> = (0..PLAYERS)
+        .map(|number| {
+            let address = address.clone();
+            std::thread::spawn(move || play(&address, number, seconds))
+        })
+        .collect();
+    let mut total = Tally::default();
+    for bot in bots {
+        match bot.join().unwrap() {
+            Ok(tally) => {
+                total.bytes += tally.bytes;
+                total.messages += tally.messages;
+                for (kind, (count, bytes)) in tally.by_kind {
+                    let entry = total.by_kind.entry(kind).or_default();
+                    entry.0 += count;
+                    entry.1 += bytes;
+                }
+            }
+            Err(e) => {
+                eprintln!("a bot failed: {}", e);
+                return;
+            }
+        }
+    }
+    let per_second = |n: usize| n as f64 / PLAYERS as f64 / seconds as f64;
+    println!(
+        "per client: {:.1} KiB/s in {:.0} messages/s",
+        per_second(total.bytes) / 1024.0,
+        per_second(total.messages)
+    );
+    let mut kinds: Vec<_> = total.by_kind.into_iter().collect();
+    kinds.sort_by_key(|(_, (_, bytes))| std::cmp::Reverse(*bytes));
+    for (kind, (count, bytes)) in kinds {
+        println!(
+            "    {:<16} {:>8.1} KiB/s {:>8.0} messages/s",
+            kind,
+            per_second(bytes) / 1024.0,
+            per_second(count)
+        );
+    }
+}
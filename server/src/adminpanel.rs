@@ -27,6 +27,10 @@ struct AdminWidget {
     connected_clients: usize,
     players: usize,
     max_players: usize,
+    mean_rtt: Option<Duration>,
+    queued_kib: usize,
+    deepest_queue_kib: usize,
+    overflow_disconnects: u64,
     tick: u16,
     time_in_stage: u16,
     stage_name: String,
@@ -34,6 +38,8 @@ struct AdminWidget {
     all_bullets: usize,
     all_sensors: usize,
     all_pieces: usize,
+    chat: Vec<Line<'static>>,
+    chat_scroll: usize, // how many lines back from the newest message we're looking
 }
 
 impl AdminWidget {
@@ -43,10 +49,13 @@ impl AdminWidget {
         name: Res<ConfigFileName>,
         clients: Query<&Client>,
         playing: Query<&ClientPlaying>,
+        latencies: Query<&ClientLatency>,
+        network: Res<NetworkMetrics>,
         entity_data: &Entities,
         bullets: Query<&Bullet>,
         sensors: Query<&FieldSensor>,
         pieces: Query<&GamePiece>,
+        chat_log: Res<ChatLog>,
     ) -> Self {
         let name: &ConfigFileName = &name;
         Self {
@@ -57,6 +66,17 @@ impl AdminWidget {
             connected_clients: clients.iter().len(),
             players: playing.iter().len(),
             max_players: config.counts.max_players as usize,
+            mean_rtt: if latencies.is_empty() {
+                None
+            } else {
+                Some(
+                    latencies.iter().map(|l| l.rtt).sum::<Duration>()
+                        / latencies.iter().len() as u32,
+                )
+            },
+            queued_kib: network.queued_bytes / 1024,
+            deepest_queue_kib: network.deepest_queue / 1024,
+            overflow_disconnects: network.overflow_disconnects,
             stage_name: match state.get_state_enum() {
                 Stage::MoveShips => "MOVE SHIPS",
                 Stage::Playing => "PLAYING",
@@ -69,6 +89,17 @@ impl AdminWidget {
             all_bullets: bullets.iter().len(),
             all_sensors: sensors.iter().len(),
             all_pieces: pieces.iter().len(),
+            chat: chat_log
+                .iter()
+                .map(|entry| {
+                    Line::from(vec![
+                        format!("[{}] ", entry.channel.get_str()).dim(),
+                        format!("{}: ", entry.nickname).bold(),
+                        entry.text.clone().into(),
+                    ])
+                })
+                .collect(),
+            chat_scroll: 0,
         }
     }
 }
@@ -77,6 +108,7 @@ impl AdminWidget {
 struct AdminPanel {
     terminal: DefaultTerminal,
     exit: bool,
+    chat_scroll: usize,
 }
 
 impl AdminPanel {
@@ -84,10 +116,13 @@ impl AdminPanel {
         Self {
             terminal: ratatui::init(),
             exit: false,
+            chat_scroll: 0,
         }
     }
 
-    fn tick(&mut self, widget: AdminWidget) -> bool {
+    fn tick(&mut self, mut widget: AdminWidget) -> bool {
+        self.chat_scroll = self.chat_scroll.min(widget.chat.len().saturating_sub(1));
+        widget.chat_scroll = self.chat_scroll;
         self.terminal
             .draw(|frame| Self::draw(frame, widget))
             .unwrap();
@@ -101,6 +136,22 @@ impl AdminPanel {
                         KeyCode::Char('q') => {
                             self.exit = true;
                         }
+                        // scroll the chat log. it sticks to the newest messages unless you scroll back
+                        KeyCode::Up => {
+                            self.chat_scroll += 1;
+                        }
+                        KeyCode::Down => {
+                            self.chat_scroll = self.chat_scroll.saturating_sub(1);
+                        }
+                        KeyCode::PageUp => {
+                            self.chat_scroll += 10;
+                        }
+                        KeyCode::PageDown => {
+                            self.chat_scroll = self.chat_scroll.saturating_sub(10);
+                        }
+                        KeyCode::End => {
+                            self.chat_scroll = 0;
+                        }
                         _ => {}
                     }
                 }
@@ -117,8 +168,8 @@ impl AdminPanel {
 
 impl Widget for &AdminWidget {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        let [title, stats, _] = Layout::vertical([Length(1), Max(9), Min(0)]).areas(area);
-        let [quick_stats, _] = Layout::horizontal([Max(30), Fill(1)]).areas(stats);
+        let [title, stats, chat] = Layout::vertical([Length(1), Max(12), Min(0)]).areas(area);
+        let [quick_stats, _] = Layout::horizontal([Max(36), Fill(1)]).areas(stats);
         Line::from(vec![
             "Exosphere Admin Panel  ".bold(),
             (&self.config_filename).into(),
@@ -138,6 +189,22 @@ impl Widget for &AdminWidget {
                 "/".into(),
                 self.max_players.to_string().into(),
             ]),
+            Line::from(vec![
+                "Mean RTT: ".into(),
+                match self.mean_rtt {
+                    Some(rtt) => format!("{}ms", rtt.as_millis()).bold(),
+                    None => "-".bold(),
+                },
+            ]),
+            Line::from(vec![
+                "Send Queue: ".into(),
+                format!("{}KiB", self.queued_kib).bold(),
+                format!(" (max {}KiB)", self.deepest_queue_kib).into(),
+            ]),
+            Line::from(vec![
+                "Dropped Slow Clients: ".into(),
+                self.overflow_disconnects.to_string().bold(),
+            ]),
             Line::from(vec![
                 (&self.stage_name).into(),
                 " ".into(),
@@ -164,6 +231,23 @@ impl Widget for &AdminWidget {
         ])
         .block(stats_bar)
         .render(quick_stats, buf);
+        let chat_box = Block::default()
+            .borders(Borders::ALL)
+            .title(if self.chat_scroll > 0 {
+                format!(
+                    "Chat [{} lines back; up/down to scroll, end to jump back]",
+                    self.chat_scroll
+                )
+            } else {
+                "Chat [up/down to scroll]".to_string()
+            });
+        // pin the newest line to the bottom of the box, minus however far we've scrolled back
+        let visible = chat.height.saturating_sub(2) as usize;
+        let top = self.chat.len().saturating_sub(visible + self.chat_scroll);
+        Paragraph::new(self.chat.clone())
+            .scroll((top as u16, 0))
+            .block(chat_box)
+            .render(chat, buf);
     }
 }
 
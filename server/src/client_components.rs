@@ -11,8 +11,9 @@
 */
 
 use bevy::prelude::*;
-use common::comms::ServerMessage;
-use common::PlayerId;
+use common::comms::{Features, RejectionReason, ServerMessage};
+use common::{PieceId, PlayerId};
+use std::collections::HashMap;
 
 #[derive(Component)]
 pub struct ClientMoney {
@@ -38,11 +39,63 @@ pub struct Client {
     pub id: PlayerId, // don't use this for anything else!
 }
 
+#[derive(Component)]
+pub struct ClientLatency {
+    pub rtt: std::time::Duration, // smoothed round-trip time, measured by websocket heartbeat pings
+}
+
 #[derive(Component)]
 pub struct ClientMeta {
     pub nickname: String,
     pub id: PlayerId,
+    pub resume_token: u128, // secret handed to the client in Metadata. single-use; it's replaced every time it's used
+}
+
+#[derive(Component)]
+pub struct ClientDormant {
+    pub expires: std::time::Instant, // the connection dropped; if nobody resumes by this time, the player is gone for good
+}
+#[derive(Component)]
+pub struct ClientSnapshotAck {
+    pub acked: u32, // the last snapshot the client says it applied. 0 for none: it gets a full snapshot
+}
+
+#[derive(Component)]
+pub struct ClientFeatures(pub Features); // the optional protocol features the client and server agreed on in their hellos
+
+#[derive(Component)]
+pub struct ClientViewport(pub Rect); // the part of the board the client last said it's looking at
+
+#[derive(Component, Default)]
+pub struct ClientInterest {
+    pub region: Option<Rect>, // where the client's being sent updates from. None for everywhere
+    pub known: HashMap<PieceId, u32>, // pieces the client has been sent, and the last snapshot sequence from before it was
+    pub forgotten: HashMap<PieceId, u32>, // pieces the client has been told to delete, and the last snapshot they were in
+}
+
+impl ClientInterest {
+    pub fn sees(&self, area: Rect) -> bool {
+        self.region
+            .is_none_or(|region| !region.intersect(area).is_empty())
+    }
+}
+
+// enough to decide whether a client should hear about something happening on the board: who they are (for fog of
+// war) and where they're looking
+pub type ClientSight = (
+    &'static ClientMeta,
+    Option<&'static ClientAffiliation>,
+    &'static ClientChannel,
+    &'static ClientInterest,
+);
+
+#[derive(Component)]
+pub struct ClientChatLimiter {
+    pub window_start: std::time::Instant, // start of the current rate limiting window
+    pub sent: u32,                        // messages sent in this window
+    pub muted_until: Option<std::time::Instant>,
 }
+
 #[derive(Component)]
 pub struct ClientAffiliation {
     pub slot: u8,
@@ -51,6 +104,7 @@ pub struct ClientAffiliation {
 pub struct ClientChannel {
     pub id: PlayerId,
     pub channel: crossbeam::channel::Sender<(PlayerId, ServerMessage)>,
+    pub waker: crate::websocket::Waker,
 }
 
 impl ClientChannel {
@@ -58,5 +112,11 @@ impl ClientChannel {
         if let Err(_) = self.channel.try_send((self.id, msg)) {
             println!("failed to send message on channel");
         }
+        self.waker.wake();
+    }
+
+    // tell the client we're not doing what it asked, and why
+    pub fn reject(&self, request_id: u32, reason: RejectionReason) {
+        self.send(ServerMessage::CommandRejected { request_id, reason });
     }
 }
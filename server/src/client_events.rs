@@ -12,11 +12,13 @@
 
 use bevy::prelude::*;
 use common::comms::*;
+use common::fab::TechLine;
 use common::types::*;
 use common::PieceId;
 
 #[derive(Event)]
 pub struct ClientPlaceEvent {
+    pub request_id: u32,
     pub x: f32,
     pub y: f32,
     pub tp: PieceType,
@@ -33,6 +35,8 @@ pub struct ClientCollectEvent {
 #[derive(Event)]
 pub struct ClientConnectEvent(pub Entity, pub String); // a client tried to connect. we may need to reject or challenge
 #[derive(Event)]
+pub struct ClientResumeEvent(pub Entity, pub u128); // a freshly connected client wants to take over a dropped player with this token
+#[derive(Event)]
 pub struct ClientTriedPasswordEvent(pub Entity, pub String); // a client tried to use the password
 #[derive(Event)]
 pub struct ClientTriedTeamConnectEvent(pub Entity, pub u8, pub String); // a client tried to add itself to a team
@@ -43,10 +47,21 @@ pub struct ClientRequestedSpectateEvent(pub Entity); // a client now wants to sp
 pub struct ClientSuccessfullyJoinedEvent(pub Entity); // whatever the cause, a client now needs to receive its metadata frame
                                                       // and all of the object data on the board
 #[derive(Event)]
-pub struct ClientSpecialObjectEvent(pub Entity, pub PieceId, pub ObjectSpecialPropertySet); // set some special property. this is for stuff like gun states and constructor positioning
+pub struct ClientSpecialObjectEvent(
+    pub Entity,
+    pub u32,
+    pub PieceId,
+    pub ObjectSpecialPropertySet,
+); // (client, request id, piece, what) set some special property. this is for stuff like gun states and constructor positioning
+
+#[derive(Event)]
+pub struct ClientUpgradeFabberEvent(pub Entity, pub u32, pub PieceId, pub TechLine); // (client, request id, fabber, which line) buy a fabber level
+
+#[derive(Event)]
+pub struct ClientChatEvent(pub Entity, pub ChatChannel, pub String); // a client said something on a chat channel
 
 #[derive(Event)]
-pub struct StrategyPathModifiedEvent(pub Entity, pub StrategyPathModification);
+pub struct StrategyPathModifiedEvent(pub Entity, pub u32, pub StrategyPathModification); // (client, request id, modification)
 
 #[derive(Event)]
 pub struct ClientLostEvent;
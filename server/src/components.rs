@@ -57,6 +57,16 @@ impl GamePiece {
             health: health,
         }
     }
+
+    pub(crate) fn allied_with(&self, other: &GamePiece) -> bool {
+        allies((self.owner, self.slot), (other.owner, other.slot))
+    }
+}
+
+// whether two (owner, slot)s are on the same side: the same player, or teammates. slot 1 is for free agents, so sharing
+// it doesn't make you a team
+pub(crate) fn allies((owner, slot): (PlayerId, u8), (other, other_slot): (PlayerId, u8)) -> bool {
+    owner == other || (slot > 1 && slot == other_slot)
 }
 
 #[derive(Component)]
@@ -69,6 +79,19 @@ impl Territory {
     pub(crate) fn castle() -> Self {
         Self { radius: 600.0 }
     }
+
+    pub(crate) fn fort() -> Self {
+        Self { radius: 400.0 }
+    }
+
+    pub(crate) fn of(tp: PieceType) -> Option<Self> {
+        // the territory a building holds, if it holds any
+        match tp {
+            PieceType::Castle => Some(Self::castle()),
+            PieceType::Fort => Some(Self::fort()),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Component)]
@@ -93,6 +116,34 @@ impl Fabber {
         }
     }
 
+    pub(crate) fn fort() -> Self {
+        Self {
+            // Medium-M2S1E2D2B1
+            radius: 350.0,
+            levels: FabLevels {
+                missiles: 2,
+                ships: 1,
+                econ: 2,
+                defense: 2,
+                buildings: 1,
+            },
+        }
+    }
+
+    pub(crate) fn outpost() -> Self {
+        Self {
+            // Small-M0S0E2D1B0
+            radius: 200.0,
+            levels: FabLevels {
+                missiles: 0,
+                ships: 0,
+                econ: 2,
+                defense: 1,
+                buildings: 0,
+            },
+        }
+    }
+
     pub(crate) fn is_available(&self, tp: PieceType) -> bool {
         // determine if this fabber can produce an object
         self.levels >= tp.fabber()
@@ -212,6 +263,22 @@ impl Gun {
         }
     }
 
+    pub(crate) fn blast() -> Self {
+        Self {
+            enabled: true,
+            cd: 30,
+            bullets: Bullets::GlideBomb(ExplosionProperties::large(), 150),
+            repeats: 0,
+            repeat_cd: 0,
+            r_point: 0,
+            tick: 1,
+            barrels: 1,
+            barrel_spacing: 0.0,
+            center_offset: 40.0,
+            scatter_barrels: false,
+        }
+    }
+
     pub(crate) fn extended_barrels(mut self, num: u16, spacing: f32) -> Self {
         self.barrels += num;
         self.barrel_spacing = spacing;
@@ -248,6 +315,29 @@ pub(crate) struct CollisionExplosion {
     pub(crate) explosion: ExplosionProperties,
 }
 
+#[derive(Component)]
+pub(crate) struct ClusterWarhead {
+    // splits into a swarm of bombs when it dies, at the end of its path or whenever it gets hit
+    pub(crate) bombs: u16,
+    pub(crate) bomb: Bullets,
+    pub(crate) radius: f32, // how far out it looks for enemies to split up between
+    pub(crate) armed: bool, // set once it's actually going somewhere, so it doesn't pop right where it was placed
+}
+
+impl ClusterWarhead {
+    pub(crate) fn madecs() -> Self {
+        Self {
+            bombs: 20,
+            bomb: Bullets::GlideBomb(ExplosionProperties::large(), 120),
+            radius: 600.0,
+            armed: false,
+        }
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct ClusterBomb(pub(crate) Entity); // the warhead a bomb came out of. bombs from the same one leave each other alone
+
 #[derive(Copy, Clone, Component)]
 pub struct ExplosionProperties {
     pub radius: f32,
@@ -261,6 +351,13 @@ impl ExplosionProperties {
             damage: 2.0,
         }
     }
+
+    pub fn large() -> Self {
+        Self {
+            radius: 150.0,
+            damage: 3.0,
+        }
+    }
 }
 
 #[derive(Component)]
@@ -521,6 +618,40 @@ impl ScrapShip {
     }
 }
 
+#[derive(Component)]
+pub struct PointDefense {
+    // shoots down incoming bullets and missiles with short lasers
+    pub in_range: Vec<Entity>,
+    pub range: f32,
+    pub dmg: f32,
+    pub cd: u16,
+    pub tick: u16,
+}
+
+impl PointDefense {
+    pub fn new(range: f32, dmg: f32, cd: u16) -> Self {
+        Self {
+            in_range: vec![],
+            range,
+            dmg,
+            cd,
+            tick: 0,
+        }
+    }
+
+    pub fn enter(&mut self, e: Entity) {
+        if !self.in_range.contains(&e) {
+            self.in_range.push(e);
+        }
+    }
+
+    pub fn leave(&mut self, e: Entity) {
+        if let Some(index) = self.in_range.iter().position(|x| *x == e) {
+            self.in_range.swap_remove(index);
+        }
+    }
+}
+
 pub trait TargetingAlgorithm {
     fn will_attack(&self, tp: PieceType) -> bool;
 
@@ -546,7 +677,17 @@ impl TargetingAlgorithm for StandardTargeting {
     }
 }
 
-pub struct SmartTargeting;
+pub struct SmartTargeting {
+    pub bullet_speed: f32, // how fast whatever we're shooting moves, so we can lead properly
+}
+
+impl SmartTargeting {
+    pub fn new(bullets: Bullets) -> Self {
+        Self {
+            bullet_speed: bullets.speed(),
+        }
+    }
+}
 
 impl TargetingAlgorithm for SmartTargeting {
     fn will_attack(&self, tp: PieceType) -> bool {
@@ -558,8 +699,8 @@ impl TargetingAlgorithm for SmartTargeting {
 
     fn get_target_angle(&self, off_vec: Vec2, vel: Vec2) -> f32 {
         // see https://www.gamedev.net/forums/topic/582894-target-leading-in-2d/
-        const BULLET_SPEED: f32 = 450.0 / 30.0;
-        let a = vel.dot(vel) - BULLET_SPEED * BULLET_SPEED;
+        let bullet_speed = self.bullet_speed / 30.0;
+        let a = vel.dot(vel) - bullet_speed * bullet_speed;
         let b = 2.0 * vel.dot(off_vec);
         let c = off_vec.dot(off_vec);
 
@@ -610,6 +751,105 @@ impl Turret {
     }
 }
 
+#[derive(Component)]
+pub struct Constructor {
+    // follows paths like any other ship until it's told to build, then sits still (and vulnerable) until it's done
+    pub builds: PieceType,
+    pub build_time: u16,
+    pub remaining: Option<u16>, // play ticks left on the build, if it's started
+    pub stages: u8, // builds left before it's used up. after every build but the last it's free to move on to the next site
+}
+
+impl Constructor {
+    pub fn new(builds: PieceType) -> Self {
+        Self {
+            builds,
+            build_time: 1800,
+            remaining: None,
+            stages: 1,
+        }
+    }
+
+    pub fn teleportal() -> Self {
+        // one portal at each end, each in half the usual time
+        Self {
+            builds: PieceType::Teleportal,
+            build_time: 900,
+            remaining: None,
+            stages: 2,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Teleportal {
+    // one end of a teleportal pair. ships that wander into its field come out at the other end
+    pub builder: Option<Entity>, // the constructor that made us. both ends share it until they find each other
+    pub link: Option<Entity>,    // the other end, once we have it
+    pub entered: Vec<Entity>,    // ships that came into the field since the last tick
+}
+
+impl Teleportal {
+    pub fn new(builder: Option<Entity>) -> Self {
+        Self {
+            builder,
+            link: None,
+            entered: vec![],
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct TeleportCooldown(pub u16); // ticks before this ship can go through a teleportal again. keeps it from bouncing straight back
+
+#[derive(Component)]
+pub struct EmpZone {
+    // fries bullets and bogs down enemy ships in its field
+    pub slow: f32, // the fraction of their thrust enemy ships get to keep while they're inside
+    pub affected: Vec<Entity>,
+}
+
+impl EmpZone {
+    pub fn new(slow: f32) -> Self {
+        Self {
+            slow,
+            affected: vec![],
+        }
+    }
+
+    pub fn enter(&mut self, e: Entity) {
+        if !self.affected.contains(&e) {
+            self.affected.push(e);
+        }
+    }
+
+    pub fn leave(&mut self, e: Entity) {
+        if let Some(index) = self.affected.iter().position(|x| *x == e) {
+            self.affected.swap_remove(index);
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct LaserEmitter {
+    // fires a laser at whatever its turret is tracking, once it's fully charged
+    pub range: f32,
+    pub dmg: f32,
+    pub charge_time: u16,
+    pub charge: u16,
+}
+
+impl LaserEmitter {
+    pub fn new(range: f32, dmg: f32, charge_time: u16) -> Self {
+        Self {
+            range,
+            dmg,
+            charge_time,
+            charge: 0,
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct PresolveVelocity(pub Vec2); // the velocity before the last solver step, useful for collision handling
 
@@ -2,6 +2,7 @@ use bevy_ecs_macros::Resource;
 use common::PlayerId;
 // config file parser
 use serde_derive::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
 
 use crate::placer::Placer;
 
@@ -79,6 +80,92 @@ pub struct TeamDescriptor {
     pub password: String,
 }
 
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    pub max_message_size: usize, // bytes; the largest websocket message (after reassembling fragments) a client may send
+    pub close_timeout_ms: u64, // how long to wait for a client to answer our close frame before dropping it
+    pub heartbeat_interval_ms: u64, // how often clients are pinged (this is also how often rtt is measured)
+    pub idle_timeout_ms: u64, // clients that haven't sent anything, pongs included, for this long are dropped
+    pub max_queued_bytes: usize, // per-client send queue budget. position updates are coalesced; everything else queues
+    pub backpressure_grace_ms: u64, // how long a client may stay over max_queued_bytes before it's dropped
+    pub static_dir: Option<String>, // if set, plain GET requests (anything but /game) are served files from here
+    pub tls_cert: Option<String>, // pem certificate chain. if this and tls_key are set, everything is served over tls.
+    pub tls_key: Option<String>,  // pem private key. needs a server built with the tls feature
+    pub resume_grace_ms: u64, // how long a dropped player's pieces stick around waiting for them to resume. 0 disables resuming
+    pub area_of_interest: bool, // only send clients what's near the viewport they report. if false, everyone gets everything
+    pub interest_margin: f32, // how far past the edge of the viewport pieces are sent. pieces are dropped at twice this
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        let ws = crate::websocket::Settings::default();
+        Self {
+            max_message_size: ws.max_message_size,
+            close_timeout_ms: ws.close_timeout.as_millis() as u64,
+            heartbeat_interval_ms: ws.heartbeat_interval.as_millis() as u64,
+            idle_timeout_ms: ws.idle_timeout.as_millis() as u64,
+            max_queued_bytes: ws.max_queued_bytes,
+            backpressure_grace_ms: ws.backpressure_grace.as_millis() as u64,
+            static_dir: None,
+            tls_cert: None,
+            tls_key: None,
+            resume_grace_ms: 30000,
+            area_of_interest: true,
+            interest_margin: 400.0,
+        }
+    }
+}
+
+impl NetworkConfig {
+    pub fn to_websocket_settings(&self) -> Result<crate::websocket::Settings, String> {
+        #[cfg(not(feature = "tls"))]
+        if self.tls_cert.is_some() || self.tls_key.is_some() {
+            return Err(
+                "tls_cert and tls_key need a server built with the tls feature".to_string(),
+            );
+        }
+        Ok(crate::websocket::Settings {
+            max_message_size: self.max_message_size,
+            close_timeout: std::time::Duration::from_millis(self.close_timeout_ms),
+            heartbeat_interval: std::time::Duration::from_millis(self.heartbeat_interval_ms),
+            idle_timeout: std::time::Duration::from_millis(self.idle_timeout_ms),
+            max_queued_bytes: self.max_queued_bytes,
+            backpressure_grace: std::time::Duration::from_millis(self.backpressure_grace_ms),
+            static_dir: self.static_dir.as_ref().map(std::path::PathBuf::from),
+            greeting_mismatch: None,
+            #[cfg(feature = "tls")]
+            tls: match (&self.tls_cert, &self.tls_key) {
+                (Some(cert), Some(key)) => Some(crate::tls::load_config(cert, key)?),
+                (None, None) => None,
+                _ => return Err("tls_cert and tls_key have to be set together".to_string()),
+            },
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct ChatConfig {
+    pub enabled: bool,
+    pub max_length: usize, // characters. longer messages are cut off
+    pub rate_limit: u32, // messages a client may send per rate_window_ms; anything past that is dropped
+    pub rate_window_ms: u64,
+    pub mute_ms: u64, // clients that hit the rate limit are muted for this long. 0 just drops the extra messages
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_length: 200,
+            rate_limit: 5,
+            rate_window_ms: 5000,
+            mute_ms: 30000,
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Resource)]
 pub struct Config {
     pub game_address: String,
@@ -89,28 +176,181 @@ pub struct Config {
     pub client_setup: ClientSetupConfig,
     pub game_type: String, // "io" or "normal"
     pub teams: Option<Vec<TeamDescriptor>>,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    pub chat: ChatConfig,
+    #[serde(default)]
+    pub fog_of_war: bool, // players only see enemy pieces within sight of their own (or their team's)
 }
 
-pub fn read_config() -> Option<(Config, String)> {
-    let args = std::env::args().collect::<Vec<String>>();
-    let file_name = if let Some(f) = args.get(1) {
-        f
-    } else {
-        "config.json"
-    };
-    let file = std::fs::File::open(file_name).ok()?;
-    Some((serde_json::from_reader(file).ok()?, file_name.to_string()))
+const USAGE: &str = "usage: exosphere [OPTIONS] [CONFIG_FILE]
+
+options:
+    -c, --config <FILE>       load the game config from FILE (default: config.json)
+    -b, --bind <IP>           listen on IP (like 0.0.0.0 or ::) instead of the host in the config's game_address
+    -p, --port <PORT>         listen on PORT instead of the port in the config's game_address
+    -s, --static-dir <DIR>    serve the web client's files from DIR on the game port (e.g. ../client)
+        --print-default-config
+                              print the built-in default config as json and exit
+    -h, --help                print this message and exit";
+
+// command line arguments. anything set here overrides the config file.
+#[derive(Default)]
+pub struct Args {
+    pub config_file: Option<String>,
+    pub bind: Option<String>,
+    pub port: Option<u16>,
+    pub static_dir: Option<String>,
+    pub print_default_config: bool,
+    pub help: bool,
 }
 
-pub fn read_config_or_default() -> (Config, Option<String>) {
-    if let Some((config, name)) = read_config() {
-        (config, Some(name))
-    } else {
+impl Args {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut ret = Self::default();
+        while let Some(arg) = args.next() {
+            // support both `--flag value` and `--flag=value`
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let mut value = |name: &str| {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or(format!("missing value for {}", name))
+            };
+            match flag.as_ref() {
+                "-c" | "--config" => {
+                    ret.config_file = Some(value(&flag)?);
+                }
+                "-b" | "--bind" => {
+                    ret.bind = Some(value(&flag)?);
+                }
+                "-p" | "--port" => {
+                    let port = value(&flag)?;
+                    ret.port = Some(
+                        port.parse()
+                            .map_err(|_| format!("invalid port number {}", port))?,
+                    );
+                }
+                "-s" | "--static-dir" => {
+                    ret.static_dir = Some(value(&flag)?);
+                }
+                "--print-default-config" => {
+                    ret.print_default_config = true;
+                }
+                "-h" | "--help" => {
+                    ret.help = true;
+                }
+                _ => {
+                    if flag.starts_with('-') {
+                        return Err(format!("unknown option {}", flag));
+                    }
+                    // a bare positional argument is the config file, for compatibility with the old `exosphere config.json`
+                    if ret.config_file.is_some() {
+                        return Err(format!("unexpected argument {}", flag));
+                    }
+                    ret.config_file = Some(flag);
+                }
+            }
+        }
+        Ok(ret)
+    }
+
+    pub fn from_env() -> Self {
+        match Self::parse(std::env::args().skip(1)) {
+            Ok(args) => {
+                if args.help {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                args
+            }
+            Err(e) => {
+                eprintln!("error: {}\n{}", e, USAGE);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    pub fn apply(&self, config: &mut Config) -> Result<(), String> {
+        // game_address is "host:port" (or "[v6 host]:port"); --bind and --port replace each half independently
+        if self.bind.is_some() || self.port.is_some() {
+            let (host, port) = match config.game_address.rsplit_once(':') {
+                Some((host, port)) => (host, port),
+                None => (config.game_address.as_str(), "3000"),
+            };
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            let port = match self.port {
+                Some(port) => port,
+                None => port
+                    .parse()
+                    .map_err(|_| format!("invalid port in game_address {}", config.game_address))?,
+            };
+            config.game_address = match &self.bind {
+                Some(bind) => {
+                    let ip: IpAddr = bind.parse().map_err(|_| {
+                        format!(
+                            "invalid --bind address {} (expected an IP, like 0.0.0.0 or ::)",
+                            bind
+                        )
+                    })?;
+                    SocketAddr::new(ip, port).to_string()
+                }
+                // the config's host can be a name, which only needs its port swapping
+                None => match host.parse::<IpAddr>() {
+                    Ok(ip) => SocketAddr::new(ip, port).to_string(),
+                    Err(_) => format!("{}:{}", host, port),
+                },
+            };
+        }
+        if let Some(dir) = &self.static_dir {
+            config.network.static_dir = Some(dir.clone());
+        }
+        Ok(())
+    }
+}
+
+pub fn read_config(file_name: &str) -> Result<Config, String> {
+    let file = std::fs::File::open(file_name)
+        .map_err(|e| format!("couldn't open {}: {}", file_name, e))?;
+    serde_json::from_reader(file).map_err(|e| format!("couldn't parse {}: {}", file_name, e))
+}
+
+pub fn read_config_or_default(args: &Args) -> (Config, Option<String>) {
+    if args.print_default_config {
         println!(
-            "WARNING: no config file specified or config file couldn't be opened; using defaults!"
+            "{}",
+            serde_json::to_string_pretty(&Config::default()).unwrap()
         );
-        (Config::default(), None)
+        std::process::exit(0);
+    }
+    let (mut config, name) = match &args.config_file {
+        // if a config file was asked for by name, failing to load it is fatal
+        Some(name) => match read_config(name) {
+            Ok(config) => (config, Some(name.clone())),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => match read_config("config.json") {
+            Ok(config) => (config, Some("config.json".to_string())),
+            Err(_) => {
+                println!("WARNING: no config file specified or config file couldn't be opened; using defaults!");
+                (Config::default(), None)
+            }
+        },
+    };
+    if let Err(e) = args.apply(&mut config) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
     }
+    (config, name)
 }
 
 impl Default for Config {
@@ -136,6 +376,9 @@ impl Default for Config {
                 things: vec![],
             },
             teams: None,
+            network: NetworkConfig::default(),
+            chat: ChatConfig::default(),
+            fog_of_war: false,
             client_setup: ClientSetupConfig {
                 money: 100,
                 area: vec![
@@ -174,3 +417,44 @@ impl Default for Config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn applied(game_address: &str, args: &[&str]) -> Result<String, String> {
+        let mut config = Config {
+            game_address: game_address.to_string(),
+            ..Config::default()
+        };
+        Args::parse(args.iter().map(|arg| arg.to_string()))?.apply(&mut config)?;
+        Ok(config.game_address)
+    }
+
+    #[test]
+    fn bind_and_port() {
+        assert_eq!(applied("0.0.0.0:3000", &[]).unwrap(), "0.0.0.0:3000");
+        assert_eq!(
+            applied("0.0.0.0:3000", &["--port", "4000"]).unwrap(),
+            "0.0.0.0:4000"
+        );
+        assert_eq!(
+            applied("0.0.0.0:3000", &["--bind", "127.0.0.1"]).unwrap(),
+            "127.0.0.1:3000"
+        );
+        assert_eq!(
+            applied("0.0.0.0:3000", &["--bind", "::"]).unwrap(),
+            "[::]:3000"
+        );
+        assert_eq!(
+            applied("[::]:3000", &["--port", "4000"]).unwrap(),
+            "[::]:4000"
+        );
+        assert_eq!(
+            applied("localhost:3000", &["-p", "4000"]).unwrap(),
+            "localhost:4000"
+        );
+        assert!(applied("0.0.0.0:3000", &["--bind", "0.0.0.0:4000"]).is_err());
+        assert!(applied("0.0.0.0:3000", &["--bind", "example.com"]).is_err());
+    }
+}
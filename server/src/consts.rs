@@ -12,7 +12,7 @@
 
 // constants
 
-pub const UPDATE_RATE : u64 = 30; // 30hz by default
+pub use common::UPDATE_RATE; // 30hz by default
 pub const FRAME_TIME : std::time::Duration = std::time::Duration::from_millis(1000 / UPDATE_RATE); // milliseconds per frame
 
 pub const MAX_FRAME_SIZE : usize = 1024; // maximum size of an incoming websocket frame
\ No newline at end of file
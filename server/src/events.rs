@@ -21,11 +21,6 @@ use bevy::prelude::Vec2;
 use common::types::PieceType;
 use common::PlayerId;
 
-#[derive(Event)]
-pub(crate) struct NewClientEvent {
-    pub(crate) id: PlayerId,
-}
-
 #[derive(Event)]
 pub(crate) struct PlaceEvent {
     pub(crate) x: f32,
@@ -35,6 +30,15 @@ pub(crate) struct PlaceEvent {
     pub(crate) slot: u8,
     pub(crate) tp: PieceType,
     pub(crate) free: bool, // do we need to fabber check this one? if free is set to true, fabber and territory checks are skipped
+    pub(crate) request_id: Option<u32>, // set if a client asked for (and paid for) this one: it's told, and refunded, if it fails the checks
+    pub(crate) builder: Option<Entity>, // the constructor ship this came out of, if it came out of one
+}
+
+#[derive(Event)]
+pub(crate) struct ClientDroppedEvent {
+    // a client's connection went away. if it was lost rather than closed on purpose, the player gets a grace period to resume
+    pub(crate) client: PlayerId,
+    pub(crate) resumable: bool,
 }
 
 #[derive(Event)]
@@ -50,6 +54,9 @@ pub(crate) struct ExplosionEvent {
     pub(crate) x: f32,
     pub(crate) y: f32,
     pub(crate) props: ExplosionProperties,
+    pub(crate) source: Entity, // whatever blew up. the explosion is only shown to clients who could see it
+    pub(crate) owner: Option<(PlayerId, u8)>, // the owner and slot of the source, if it was a piece
+    pub(crate) spares: Option<Entity>, // a cluster warhead whose bombs this doesn't hurt, when it's one of them
 }
 
 #[derive(Event)]
@@ -74,3 +81,10 @@ pub(crate) struct PieceHarmEvent {
     pub(crate) harm_amount: f32,
     pub(crate) responsible: PlayerId,
 }
+
+#[derive(Event)]
+pub(crate) struct RepairEvent {
+    // the owner asked for a piece to be fixed up all the way, right now
+    pub(crate) piece: Entity,
+    pub(crate) request_id: u32,
+}
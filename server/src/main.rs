@@ -32,17 +32,21 @@ use std::collections::HashMap;
 pub use common::comms;
 use common::types::*;
 use common::PlayerId;
-use common::VERSION;
-use comms::{ClientMessage, ServerMessage};
+use common::{MAGIC, PROTOCOL};
+use comms::{ClientMessage, DisconnectReason, Features, ProtocolVersion, ServerMessage};
 
 pub enum Comms {
     // webserver -> game engine
     ClientConnect(
         PlayerId,
         crossbeam::channel::Sender<(PlayerId, ServerMessage)>,
-    ), // (client) a client connected
-    ClientDisconnect(PlayerId),           // (id) a client disconnected
+        websocket::Waker,
+        Features,
+    ), // (client, channel, waker, features) a client connected and finished its hello
+    ClientDisconnect(PlayerId, websocket::CloseReason), // (id, why) a client disconnected
     MessageFrom(PlayerId, ClientMessage), // (id, message) a client sent a message that was successfully decoded and filtered
+    ClientLatency(PlayerId, std::time::Duration), // (id, rtt) a client's measured round-trip time changed
+    QueueMetrics(websocket::QueueMetrics),        // periodic send queue statistics
 }
 
 pub mod solve_spaceship;
@@ -67,6 +71,9 @@ pub mod config;
 
 pub mod websocket;
 
+#[cfg(feature = "tls")]
+pub mod tls;
+
 pub mod client_components;
 
 pub mod client_events;
@@ -101,89 +108,203 @@ fn run_play_schedule(world: &mut World) {
     }
 }
 
+// checks a client's Hello against what we speak and offer. on success, these are the features the connection gets
+fn negotiate(
+    magic: &str,
+    protocol: ProtocolVersion,
+    wanted: Features,
+    offered: Features,
+) -> Result<Features, (DisconnectReason, String)> {
+    if magic != MAGIC {
+        return Err((
+            DisconnectReason::HandshakeFailed,
+            "not an exosphere client".to_string(),
+        ));
+    }
+    if protocol.major != PROTOCOL.major || protocol.minor > PROTOCOL.minor {
+        // older minor versions are fine; newer ones might count on server behaviour we don't have
+        return Err((
+            DisconnectReason::IncompatibleProtocol,
+            format!(
+                "incompatible protocol: server speaks {}, client speaks {}",
+                PROTOCOL, protocol
+            ),
+        ));
+    }
+    Ok(wanted & offered)
+}
+
+// what a client gets if the first thing it sends isn't a Hello (or doesn't decode at all): the only exosphere clients
+// that do that are the ones from before Hello, which opened with a Test
+fn pre_hello_rejection() -> (DisconnectReason, String) {
+    (
+        DisconnectReason::IncompatibleProtocol,
+        format!(
+            "incompatible protocol: server speaks {}, client is from before the hello handshake",
+            PROTOCOL
+        ),
+    )
+}
+
 fn main() {
     let (to_bevy_tx, to_bevy_rx) = crossbeam::channel::unbounded();
-    let (from_bevy_broadcast_tx, from_bevy_broadcast_rx) = crossbeam::channel::unbounded();
+    let (from_bevy_broadcast_tx, from_bevy_broadcast_rx) =
+        crossbeam::channel::unbounded::<ServerMessage>();
     let (from_bevy_specific_tx, from_bevy_specific_rx) =
         crossbeam::channel::unbounded::<(PlayerId, ServerMessage)>();
 
+    let args = config::Args::from_env();
+    let (conf, config_file_name) = config::read_config_or_default(&args);
+    let game_address = conf.game_address.clone();
+    // everything we can do, minus whatever's turned off
+    let mut offered = Features::SNAPSHOTS;
+    if conf.chat.enabled {
+        offered = offered | Features::CHAT;
+    }
+    if conf.network.resume_grace_ms > 0 {
+        offered = offered | Features::RESUME;
+    }
+    let mut network_settings = match conf.network.to_websocket_settings() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let (reason, why) = pre_hello_rejection();
+    network_settings.greeting_mismatch = Some((reason.into(), why));
+    // pokes the network thread whenever bevy has something for it to send, so it can sleep the rest of the time
+    let waker = websocket::Waker::new().expect("couldn't create the network thread's eventfd");
+    let network_waker = waker.clone();
+
     std::thread::spawn(move || {
-        let mut server = websocket::Server::new("0.0.0.0:3000").unwrap();
+        let mut server =
+            match websocket::Server::new(&game_address, network_settings, network_waker) {
+                Ok(server) => server,
+                Err(e) => {
+                    eprintln!("error: couldn't listen on {}: {}", game_address, e);
+                    std::process::exit(1);
+                }
+            };
+        println!("listening on {}", game_address);
         struct ClientProperties {
-            has_tested: bool, // successful test response received
+            has_tested: bool,   // successful hello received
+            features: Features, // what we agreed on in the hello
         }
         use std::collections::HashMap;
         let mut clients: HashMap<websocket::ClientId, ClientProperties> = HashMap::new();
+        let mut last_metrics = std::time::Instant::now();
         loop {
             server.do_poll(
                 &mut clients,
                 |id, m: ClientMessage, server, clients| {
                     let clprops = clients.get_mut(&id).unwrap();
                     if clprops.has_tested {
+                        if !clprops.features.contains(m.requires()) {
+                            return; // the client never asked for this, so it doesn't get to use it
+                        }
                         if let Err(_) = to_bevy_tx.send(Comms::MessageFrom(id.into(), m)) {
                             println!("channel failure: this is probably fatal");
                         }
                     } else {
-                        if m == ClientMessage::Test(
-                            "EXOSPHERE".to_string(),
-                            128,
-                            4096,
-                            115600,
-                            123456789012345,
-                            -64,
-                            -4096,
-                            -115600,
-                            -123456789012345,
-                            -4096.512,
-                            -8192.756,
-                            VERSION,
-                        ) {
-                            clprops.has_tested = true;
-                            if let Err(_) = to_bevy_tx.send(Comms::ClientConnect(
-                                id.into(),
-                                from_bevy_specific_tx.clone(),
-                            )) {
-                                println!("channel failure: this is probably fatal");
+                        let ClientMessage::Hello {
+                            magic,
+                            protocol,
+                            features,
+                        } = m
+                        else {
+                            let (reason, why) = pre_hello_rejection();
+                            println!("client {:?} rejected: {}", id, why);
+                            server.close(id, reason.into(), &why);
+                            return;
+                        };
+                        match negotiate(&magic, protocol, features, offered) {
+                            Ok(features) => {
+                                clprops.has_tested = true;
+                                clprops.features = features;
+                                if let Err(_) = to_bevy_tx.send(Comms::ClientConnect(
+                                    id.into(),
+                                    from_bevy_specific_tx.clone(),
+                                    server.waker(),
+                                    features,
+                                )) {
+                                    println!("channel failure: this is probably fatal");
+                                }
+                            }
+                            Err((reason, why)) => {
+                                // the close frame is the only thing we can count on a client we don't understand
+                                // being able to read, so the details go in there
+                                println!("client {:?} rejected: {}", id, why);
+                                server.close(id, reason.into(), &why);
                             }
-                        } else {
-                            server.close(id);
                         }
                     }
                 },
                 |id, server, clients| {
-                    clients.insert(id, ClientProperties { has_tested: false });
+                    clients.insert(
+                        id,
+                        ClientProperties {
+                            has_tested: false,
+                            features: Features::NONE,
+                        },
+                    );
                     server.send_to(
                         id,
-                        ServerMessage::Test(
-                            "EXOSPHERE".to_string(),
-                            128,
-                            4096,
-                            115600,
-                            123456789012345,
-                            -64,
-                            -4096,
-                            -115600,
-                            -123456789012345,
-                            -4096.512,
-                            -8192.756,
-                            VERSION,
-                        ),
+                        ServerMessage::Hello {
+                            magic: MAGIC.to_string(),
+                            protocol: PROTOCOL,
+                            features: offered,
+                        },
                     )
                 },
-                |id, clients| {
-                    if let Some(client) = clients.get(&id) {
-                        if client.has_tested {
-                            if let Err(_) = to_bevy_tx.send(Comms::ClientDisconnect(id.into())) {
-                                println!("channel failure: this is probably fatal");
-                            }
+                |id, reason, clients| {
+                    if let Some(client) = clients.remove(&id) {
+                        if client.has_tested
+                            && to_bevy_tx
+                                .send(Comms::ClientDisconnect(id.into(), reason))
+                                .is_err()
+                        {
+                            println!("channel failure: this is probably fatal");
                         }
                     }
                 },
             );
+            for (id, rtt) in server.drain_rtt_updates() {
+                if clients.get(&id).is_some_and(|client| client.has_tested)
+                    && to_bevy_tx
+                        .send(Comms::ClientLatency(id.into(), rtt))
+                        .is_err()
+                {
+                    println!("channel failure: this is probably fatal");
+                }
+            }
+            if last_metrics.elapsed() >= std::time::Duration::from_secs(1) {
+                last_metrics = std::time::Instant::now();
+                if to_bevy_tx
+                    .send(Comms::QueueMetrics(server.queue_metrics()))
+                    .is_err()
+                {
+                    println!("channel failure: this is probably fatal");
+                }
+            }
             loop {
                 match from_bevy_broadcast_rx.try_recv() {
                     Ok(message) => {
-                        server.broadcast(message);
+                        let requires = message.requires();
+                        if let ServerMessage::Disconnect { reason } = message {
+                            // send the message, then start closing everyone
+                            server.broadcast(message);
+                            server.close_all(reason.into(), reason.get_str());
+                        } else if requires == Features::NONE {
+                            server.broadcast(message);
+                        } else {
+                            // only to the clients that can make sense of it
+                            for (id, client) in clients.iter() {
+                                if client.has_tested && client.features.contains(requires) {
+                                    server.send_to(*id, message.clone());
+                                }
+                            }
+                        }
                     }
                     Err(crossbeam::channel::TryRecvError::Empty) => {
                         break;
@@ -196,11 +317,19 @@ fn main() {
             loop {
                 match from_bevy_specific_rx.try_recv() {
                     Ok((id, message)) => {
-                        if let ServerMessage::Disconnect = message {
-                            // disconnects aren't optional; we're going to send a message, then immediately drop the client
-                            server.close(id.into());
+                        let Some(client) = clients.get(&id.into()) else {
+                            continue; // the player dropped, but the game keeps them around in case they resume
+                        };
+                        if !client.features.contains(message.requires()) {
+                            continue;
+                        }
+                        if let ServerMessage::Disconnect { reason } = message {
+                            // disconnects aren't optional; we're going to send a message, then close the connection
+                            server.send_to(id.into(), message);
+                            server.close(id.into(), reason.into(), reason.get_str());
+                        } else {
+                            server.send_to(id.into(), message);
                         }
-                        server.send_to(id.into(), message);
                     }
                     Err(crossbeam::channel::TryRecvError::Empty) => {
                         break;
@@ -213,8 +342,6 @@ fn main() {
         }
     });
 
-    let (conf, config_file_name) = config::read_config_or_default();
-
     App::new()
         .add_plugins(PhysicsPlugins::default())
         .insert_resource(avian2d::dynamics::solver::SolverDiagnostics::default())
@@ -232,25 +359,34 @@ fn main() {
                 lasernodes,
                 lasers,
                 scrapships,
+                point_defense,
+                laser_turrets,
+                constructors,
+                teleportals,
+                cluster_warheads,
                 turrets,
             ),
         )
         .init_schedule(PlaySchedule)
-        .add_event::<NewClientEvent>()
         .add_event::<ClientKilledEvent>()
         .add_event::<PlaceEvent>()
         .add_event::<PieceHarmEvent>()
+        .add_event::<RepairEvent>()
         .add_event::<ExplosionEvent>()
         .add_event::<PieceDestroyedEvent>()
         .add_event::<LaserCastEvent>()
         .add_event::<ClientPlaceEvent>()
         .add_event::<ClientCollectEvent>()
         .add_event::<ClientConnectEvent>()
+        .add_event::<ClientDroppedEvent>()
+        .add_event::<ClientResumeEvent>()
+        .add_event::<ClientChatEvent>()
         .add_event::<ClientTriedPasswordEvent>()
         .add_event::<ClientTriedTeamConnectEvent>()
         .add_event::<ClientRequestedSpectateEvent>()
         .add_event::<ClientSuccessfullyJoinedEvent>()
         .add_event::<ClientSpecialObjectEvent>()
+        .add_event::<ClientUpgradeFabberEvent>()
         .add_event::<StrategyPathModifiedEvent>()
         .add_event::<ClientLostEvent>()
         .insert_resource(ClientMap(HashMap::new())) // -225, -39.5, -516.9
@@ -258,7 +394,17 @@ fn main() {
         .insert_resource(Receiver(to_bevy_rx))
         .insert_resource(Gravity(Vec2::new(0.0, 0.0)))
         .insert_resource(OneShots::default())
-        .insert_resource(Sender(from_bevy_broadcast_tx))
+        .insert_resource(NetworkMetrics::default())
+        .insert_resource(ConnectionAliases::default())
+        .insert_resource(ChatLog::default())
+        .insert_resource(Spending::default())
+        .insert_resource(SnapshotHistory::default())
+        .insert_resource(PieceIndex::default())
+        .insert_resource(Vision::default())
+        .insert_resource(Sender {
+            channel: from_bevy_broadcast_tx,
+            waker,
+        })
         .insert_resource(GameState {
             playing: false,
             io: conf.game_type == "io",
@@ -273,15 +419,20 @@ fn main() {
             Update,
             (
                 client_connection,
+                client_dropped,
+                client_resume.before(setup_client),
                 client_disconnection,
                 client_flow_password.before(setup_client),
                 client_flow_team.before(setup_client),
                 client_place,
                 setup_client,
                 special_handler,
+                fabber_upgrades,
+                repair,
                 strategy_path_handler,
                 client_win_checks,
                 client_money,
+                chat,
             )
                 .before(client_tick),
         )
@@ -291,8 +442,13 @@ fn main() {
                 client_tick,
                 #[cfg(feature = "admin_panel")]
                 adminpanel::update_admin_panel,
-                send_objects,
-                position_updates,
+                index_pieces
+                    .after(make_thing)
+                    .after(on_piece_dead)
+                    .after(client_disconnection),
+                update_vision.after(index_pieces),
+                send_objects.after(update_vision).after(setup_client),
+                position_updates.after(send_objects),
                 frame_broadcast.before(position_updates),
                 make_thing,
                 piece_harm,
@@ -1,3 +1,4 @@
+use crate::components::GamePiece;
 use crate::events::*;
 use crate::PieceType;
 use bevy::prelude::*;
@@ -6,7 +7,15 @@ use common::PlayerId;
 pub(crate) struct Placer<'a>(pub EventWriter<'a, PlaceEvent>);
 
 impl Placer<'_> {
-    pub(crate) fn p_simple(&mut self, x: f32, y: f32, client: PlayerId, slot: u8, tp: PieceType) {
+    pub(crate) fn p_simple(
+        &mut self,
+        x: f32,
+        y: f32,
+        client: PlayerId,
+        slot: u8,
+        tp: PieceType,
+        request_id: u32,
+    ) {
         self.0.write(PlaceEvent {
             x,
             y,
@@ -15,6 +24,8 @@ impl Placer<'_> {
             slot,
             tp,
             free: false,
+            request_id: Some(request_id),
+            builder: None,
         });
     }
 
@@ -34,6 +45,8 @@ impl Placer<'_> {
             slot,
             tp: PieceType::BasicFighter,
             free: true,
+            request_id: None,
+            builder: None,
         });
     }
 
@@ -46,6 +59,29 @@ impl Placer<'_> {
             slot,
             tp: PieceType::Sniper,
             free: true,
+            request_id: None,
+            builder: None,
+        });
+    }
+
+    pub(crate) fn constructed(
+        &mut self,
+        builder: Entity,
+        piece: &GamePiece,
+        transform: &Transform,
+        tp: PieceType,
+    ) {
+        // whatever a constructor ship was building, right where it's sitting
+        self.0.write(PlaceEvent {
+            x: transform.translation.x,
+            y: transform.translation.y,
+            a: transform.rotation.to_euler(EulerRot::ZYX).0,
+            owner: piece.owner,
+            slot: piece.slot,
+            tp,
+            free: true,
+            request_id: None,
+            builder: Some(builder),
         });
     }
 
@@ -58,6 +94,8 @@ impl Placer<'_> {
             slot,
             tp: PieceType::LaserNode,
             free: true,
+            request_id: None,
+            builder: None,
         });
     }
 
@@ -70,6 +108,8 @@ impl Placer<'_> {
             slot: 0,
             tp: PieceType::Chest,
             free: true,
+            request_id: None,
+            builder: None,
         });
     }
 
@@ -82,6 +122,8 @@ impl Placer<'_> {
             slot,
             tp: PieceType::Castle,
             free: true,
+            request_id: None,
+            builder: None,
         });
     }
 }
@@ -11,12 +11,13 @@
 */
 
 use crate::comms::*;
+use crate::components::{allies, ClientMoney};
 pub use crate::config::Config;
 use crate::Comms;
 use bevy::ecs::system::SystemId;
 use bevy::prelude::*;
-use common::comms::Stage;
-use common::PlayerId;
+use common::comms::{SnapshotMove, Stage};
+use common::{PieceId, PlayerId};
 use std::collections::HashMap;
 
 #[derive(Resource)]
@@ -45,11 +46,46 @@ impl GameState {
 #[derive(Resource, Deref, DerefMut)]
 pub struct ClientMap(pub HashMap<PlayerId, Entity>);
 
+// money spent this frame that client_money hasn't taken out of ClientMoney yet. everything that charges a client checks
+// here first, so two purchases in the same frame (even in different systems) can't both spend the same money
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct Spending(pub HashMap<Entity, u32>); // client -> spent, not yet collected
+
+impl Spending {
+    // sets `price` aside if the client can cover it on top of everything they've already spent. the caller still has to
+    // send the ClientCollectEvent that actually takes it
+    pub fn spend(&mut self, client: Entity, money: &ClientMoney, price: u32) -> bool {
+        let spent = self.entry(client).or_default();
+        if spent.saturating_add(price) > money.money {
+            return false;
+        }
+        *spent += price;
+        true
+    }
+}
+
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct ConnectionAliases(pub HashMap<PlayerId, PlayerId>); // resumed connection id -> the player id it took over
+
 #[derive(Resource, Deref, DerefMut)] // todo: better names (or generic type arguments)
 pub struct Receiver(pub crossbeam::channel::Receiver<Comms>);
 
-#[derive(Resource, Deref, DerefMut)]
-pub struct Sender(pub crossbeam::channel::Sender<ServerMessage>);
+#[derive(Resource)]
+pub struct Sender {
+    pub channel: crossbeam::channel::Sender<ServerMessage>,
+    pub waker: crate::websocket::Waker, // the network thread sleeps until it's woken up, so every send needs a wake
+}
+
+impl Sender {
+    pub fn send(
+        &self,
+        msg: ServerMessage,
+    ) -> Result<(), crossbeam::channel::SendError<ServerMessage>> {
+        let result = self.channel.send(msg);
+        self.waker.wake();
+        result
+    }
+}
 
 #[derive(Resource, Default)]
 pub struct OneShots {
@@ -58,3 +94,161 @@ pub struct OneShots {
 
 #[derive(Resource)]
 pub struct ConfigFileName(pub Option<String>);
+
+#[derive(Resource, Default, Deref)] // refreshed about once a second by the network thread
+pub struct NetworkMetrics(pub crate::websocket::QueueMetrics);
+
+pub struct ChatLogEntry {
+    pub nickname: String,
+    pub channel: common::comms::ChatChannel,
+    pub text: String,
+}
+
+#[derive(Resource, Default, Deref, DerefMut)] // recent chat, newest last. the admin panel shows this
+pub struct ChatLog(pub std::collections::VecDeque<ChatLogEntry>);
+
+impl ChatLog {
+    pub const CAPACITY: usize = 500;
+
+    pub fn push(&mut self, entry: ChatLogEntry) {
+        if self.0.len() >= Self::CAPACITY {
+            self.0.pop_front();
+        }
+        self.0.push_back(entry);
+    }
+}
+
+// every piece's latest sample, and the snapshot it was sampled in
+pub type SnapshotFrame = HashMap<PieceId, (SnapshotMove, u32)>;
+
+// recent world snapshots, so each client's snapshot can be a delta against whichever one it last acknowledged.
+// a client that falls further behind than this gets a full snapshot instead
+#[derive(Resource, Default)]
+pub struct SnapshotHistory {
+    sequence: u32,
+    frames: std::collections::VecDeque<(u32, SnapshotFrame)>,
+}
+
+impl SnapshotHistory {
+    pub const DEPTH: usize = 64; // about two seconds at 30hz
+
+    pub fn push(&mut self, frame: SnapshotFrame) -> u32 {
+        self.sequence += 1; // starts at 1: 0 means "no snapshot"
+        if self.frames.len() >= Self::DEPTH {
+            self.frames.pop_front();
+        }
+        self.frames.push_back((self.sequence, frame));
+        self.sequence
+    }
+
+    pub fn latest(&self) -> u32 {
+        self.sequence
+    }
+
+    pub fn get(&self, sequence: u32) -> Option<&SnapshotFrame> {
+        self.frames
+            .iter()
+            .find(|(s, _)| *s == sequence)
+            .map(|(_, frame)| frame)
+    }
+}
+
+// a coarse grid over the board, rebuilt every tick, for finding the pieces in an area without looking at all of them.
+// pieces are filed under every cell their reach (hitbox, territory, fabber or sensor radius) touches
+type IndexEntry = (Entity, Vec2, f32); // piece, position, reach
+
+#[derive(Resource, Default)]
+pub struct PieceIndex {
+    cells: HashMap<(i32, i32), Vec<IndexEntry>>,
+    owned: HashMap<PlayerId, Vec<Entity>>,
+}
+
+impl PieceIndex {
+    pub const CELL: f32 = 500.0;
+
+    fn cell(point: Vec2) -> (i32, i32) {
+        (
+            (point.x / Self::CELL).floor() as i32,
+            (point.y / Self::CELL).floor() as i32,
+        )
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.owned.clear();
+    }
+
+    pub fn insert(&mut self, entity: Entity, owner: PlayerId, pos: Vec2, reach: f32) {
+        let (min_x, min_y) = Self::cell(pos - Vec2::splat(reach));
+        let (max_x, max_y) = Self::cell(pos + Vec2::splat(reach));
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                self.cells
+                    .entry((x, y))
+                    .or_default()
+                    .push((entity, pos, reach));
+            }
+        }
+        self.owned.entry(owner).or_default().push(entity);
+    }
+
+    // every piece whose reach overlaps this area. pieces that span several cells show up more than once
+    pub fn within(&self, area: Rect) -> impl Iterator<Item = Entity> + '_ {
+        let (min_x, min_y) = Self::cell(area.min);
+        let (max_x, max_y) = Self::cell(area.max);
+        (min_x..=max_x)
+            .flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .filter(move |(_, pos, reach)| {
+                pos.max(area.min).min(area.max).distance_squared(*pos) <= reach * reach
+            })
+            .map(|(entity, _, _)| *entity)
+    }
+
+    pub fn owned_by(&self, owner: PlayerId) -> &[Entity] {
+        self.owned
+            .get(&owner)
+            .map_or(&[], |pieces| pieces.as_slice())
+    }
+}
+
+// who shares a view of the board under fog of war: a whole team (slot > 1), or a free agent on its own
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Viewer {
+    Team(u8),
+    Player(PlayerId),
+}
+
+impl Viewer {
+    pub fn of(player: PlayerId, slot: u8) -> Self {
+        if slot > 1 {
+            Self::Team(slot)
+        } else {
+            Self::Player(player)
+        }
+    }
+}
+
+// the pieces each viewer can see through the fog of war this tick. empty when fog of war is off
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct Vision(pub HashMap<Viewer, std::collections::HashSet<Entity>>);
+
+impl Vision {
+    // whether a player can see a piece through the fog of war: it's in sight, or it's theirs (or their team's).
+    // `owner` is the piece's owner and slot, if it's known. spectators, and everyone with fog of war off, see it all
+    pub fn sees(
+        &self,
+        player: PlayerId,
+        slot: u8,
+        piece: Entity,
+        owner: Option<(PlayerId, u8)>,
+    ) -> bool {
+        match self.get(&Viewer::of(player, slot)) {
+            Some(seen) => {
+                seen.contains(&piece) || owner.is_some_and(|owner| allies(owner, (player, slot)))
+            }
+            None => true,
+        }
+    }
+}
@@ -12,6 +12,7 @@
 
 // produces explosions
 
+use crate::components::*;
 use crate::events::*;
 use crate::resources::*;
 use avian2d::prelude::*;
@@ -22,18 +23,30 @@ use common::PlayerId;
 pub fn boom(
     mut commands: Commands,
     mut explosions: EventReader<ExplosionEvent>,
-    sender: ResMut<Sender>,
+    vision: Res<Vision>,
+    clients: Query<ClientSight, With<ClientConnected>>,
 ) {
     // manage explosions
     // explosions are really just sensored colliders with an explosionproperties
     for explosion in explosions.read() {
-        let _ = sender.send(ServerMessage::Explosion {
-            x: explosion.x,
-            y: explosion.y,
-            radius: explosion.props.radius,
-            damage: explosion.props.damage,
-        });
+        let area = Rect::from_center_half_size(
+            Vec2::new(explosion.x, explosion.y),
+            Vec2::splat(explosion.props.radius),
+        );
+        for (meta, affiliation, channel, interest) in clients.iter() {
+            let slot = affiliation.map_or(0, |a| a.slot);
+            if interest.sees(area) && vision.sees(meta.id, slot, explosion.source, explosion.owner)
+            {
+                channel.send(ServerMessage::Explosion {
+                    x: explosion.x,
+                    y: explosion.y,
+                    radius: explosion.props.radius,
+                    damage: explosion.props.damage,
+                });
+            }
+        }
         let damage = explosion.props.damage;
+        let spares = explosion.spares;
         commands
             .spawn((
                 RigidBody::Dynamic,
@@ -44,7 +57,16 @@ pub fn boom(
                 Sensor,
             ))
             .observe(
-                move |trigger: Trigger<OnCollisionStart>, mut hurt: EventWriter<PieceHarmEvent>| {
+                move |trigger: Trigger<OnCollisionStart>,
+                      mut hurt: EventWriter<PieceHarmEvent>,
+                      bombs: Query<&ClusterBomb>| {
+                    if spares.is_some()
+                        && bombs
+                            .get(trigger.collider)
+                            .is_ok_and(|bomb| Some(bomb.0) == spares)
+                    {
+                        return; // one of our siblings
+                    }
                     hurt.write(PieceHarmEvent {
                         piece: trigger.collider,
                         harm_amount: damage,
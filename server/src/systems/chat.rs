@@ -0,0 +1,121 @@
+/*
+    Copyright 2024 Tyler Clarke.
+
+    This file is part of Exosphere.
+
+    Exosphere is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+    Exosphere is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License along with Exosphere. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// routes chat: checks the sender is allowed on the channel (and isn't talking too much), tidies up the text,
+// and passes it along to whoever can hear that channel
+
+use crate::components::*;
+use crate::events::*;
+use crate::resources::*;
+use bevy::prelude::*;
+use common::comms::{ChatChannel, ServerMessage};
+use common::PlayerId;
+use std::time::{Duration, Instant};
+
+// which channels a client can talk on and hear: its team slot, and whether it's spectating
+type ChatStanding = (Option<&'static ClientAffiliation>, Has<ClientSpectating>);
+
+pub fn chat(
+    mut events: EventReader<ClientChatEvent>,
+    config: Res<Config>,
+    mut log: ResMut<ChatLog>,
+    mut senders: Query<(
+        &ClientMeta,
+        &ClientChannel,
+        &mut ClientChatLimiter,
+        ChatStanding,
+    )>,
+    listeners: Query<(&ClientChannel, ChatStanding), With<ClientMeta>>,
+) {
+    let now = Instant::now();
+    for ClientChatEvent(client, channel, text) in events.read() {
+        if !config.chat.enabled {
+            continue;
+        }
+        let Ok((meta, reply, mut limiter, (affiliation, spectating))) = senders.get_mut(*client)
+        else {
+            continue; // hasn't joined yet
+        };
+        // spectators get their own channel and nothing else, so they can't feed players information
+        let slot = affiliation.map_or(0, |a| a.slot);
+        let allowed = match channel {
+            ChatChannel::All => !spectating,
+            ChatChannel::Team => !spectating && slot > 1,
+            ChatChannel::Spectators => spectating,
+        };
+        if !allowed {
+            continue;
+        }
+        if let Some(until) = limiter.muted_until {
+            if now < until {
+                continue;
+            }
+            limiter.muted_until = None;
+        }
+        if now.duration_since(limiter.window_start)
+            >= Duration::from_millis(config.chat.rate_window_ms)
+        {
+            limiter.window_start = now;
+            limiter.sent = 0;
+        }
+        if limiter.sent >= config.chat.rate_limit {
+            let notice = if config.chat.mute_ms > 0 {
+                limiter.muted_until = Some(now + Duration::from_millis(config.chat.mute_ms));
+                format!(
+                    "you're sending messages too fast. muted for {}s",
+                    config.chat.mute_ms.div_ceil(1000)
+                )
+            } else {
+                "you're sending messages too fast".to_string()
+            };
+            reply.send(ServerMessage::ChatMessage {
+                from: PlayerId::SYSTEM,
+                channel: *channel,
+                text: notice,
+            });
+            continue;
+        }
+        limiter.sent += 1;
+        // one line (control characters become spaces), no more than max_length characters
+        let text: String = text
+            .trim()
+            .chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .take(config.chat.max_length)
+            .collect();
+        if text.is_empty() {
+            continue;
+        }
+        let message = ServerMessage::ChatMessage {
+            from: meta.id,
+            channel: *channel,
+            text: text.clone(),
+        };
+        for (listener, (their_affiliation, their_spectating)) in listeners.iter() {
+            let hears = match channel {
+                ChatChannel::All => true,
+                ChatChannel::Team => {
+                    !their_spectating && their_affiliation.is_some_and(|a| a.slot == slot)
+                }
+                ChatChannel::Spectators => their_spectating,
+            };
+            if hears {
+                listener.send(message.clone());
+            }
+        }
+        log.push(ChatLogEntry {
+            nickname: meta.nickname.clone(),
+            channel: *channel,
+            text,
+        });
+    }
+}
@@ -14,7 +14,7 @@ use crate::components::*;
 use crate::events::*;
 use crate::resources::*;
 use bevy::prelude::*;
-use common::comms::ServerMessage;
+use common::comms::{DisconnectReason, ServerMessage};
 
 pub fn client_connection(
     mut events: EventReader<ClientConnectEvent>,
@@ -22,16 +22,35 @@ pub fn client_connection(
     config: Res<Config>,
     channels: Query<&ClientChannel>,
     cl: Query<&Client>,
+    joined: Query<(), With<ClientMeta>>,
     mut success: EventWriter<ClientSuccessfullyJoinedEvent>,
 ) {
+    let mut player_count = joined.iter().count();
     for ClientConnectEvent(client, nickname) in events.read() {
+        if player_count >= config.counts.max_players as usize {
+            // no room. the network thread closes the socket after sending this.
+            channels
+                .get(*client)
+                .unwrap()
+                .send(ServerMessage::Disconnect {
+                    reason: DisconnectReason::ServerFull,
+                });
+            continue;
+        }
+        player_count += 1;
         let id = cl.get(*client).unwrap().id;
         commands.entity(*client).insert((
             ClientMeta {
                 id,
                 nickname: nickname.clone(),
+                resume_token: rand::random(),
             },
             ClientMoney { money: 0 },
+            ClientChatLimiter {
+                window_start: std::time::Instant::now(),
+                sent: 0,
+                muted_until: None,
+            },
         ));
         if let Some(teams) = &config.teams {
             channels
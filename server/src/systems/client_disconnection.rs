@@ -14,24 +14,17 @@ use crate::components::*;
 use crate::events::*;
 use crate::resources::*;
 use bevy::prelude::*;
-use common::comms::ServerMessage;
 
 pub fn client_disconnection(
     mut events: EventReader<ClientKilledEvent>,
     mut commands: Commands,
     pieces: Query<(Entity, &mut GamePiece)>,
-    broadcast: Res<Sender>,
     mut clients: ResMut<ClientMap>,
 ) {
     for event in events.read() {
         for (entity, piece) in pieces.iter() {
             if piece.owner == event.client {
                 commands.entity(entity).despawn();
-                if let Err(_) = broadcast.send(ServerMessage::DeleteObject { id: entity.into() }) {
-                    println!(
-                        "game engine lost connection to webserver. this is probably not critical."
-                    );
-                }
             }
         }
         if let Some(cl) = clients.get(&event.client) {
@@ -0,0 +1,60 @@
+/*
+    Copyright 2024 Tyler Clarke.
+
+    This file is part of Exosphere.
+
+    Exosphere is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+    Exosphere is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License along with Exosphere. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// decides what happens to a player whose connection went away: either it's gone for good, or it sleeps for a while in case
+// the client comes back with its resume token
+
+use crate::components::*;
+use crate::events::*;
+use crate::resources::*;
+use bevy::prelude::*;
+use common::comms::Features;
+use std::time::{Duration, Instant};
+
+pub fn client_dropped(
+    mut events: EventReader<ClientDroppedEvent>,
+    mut commands: Commands,
+    config: Res<Config>,
+    clients: Res<ClientMap>,
+    joined: Query<&ClientFeatures, (With<ClientMeta>, With<ClientAffiliation>)>,
+    dormant: Query<(Entity, &Client, &ClientDormant)>,
+    mut client_killed_event: EventWriter<ClientKilledEvent>,
+) {
+    let now = Instant::now();
+    let grace = Duration::from_millis(config.network.resume_grace_ms);
+    for ClientDroppedEvent { client, resumable } in events.read() {
+        match clients.get(client) {
+            // only players that actually made it into the game (and know how to resume) have anything worth coming back to
+            Some(entity)
+                if *resumable
+                    && !grace.is_zero()
+                    && joined
+                        .get(*entity)
+                        .is_ok_and(|features| features.0.contains(Features::RESUME)) =>
+            {
+                commands.entity(*entity).insert(ClientDormant {
+                    expires: now + grace,
+                });
+            }
+            _ => {
+                client_killed_event.write(ClientKilledEvent { client: *client });
+            }
+        }
+    }
+    for (entity, client, sleeper) in dormant.iter() {
+        if sleeper.expires <= now {
+            println!("client {:?} didn't come back in time", client.id);
+            commands.entity(entity).remove::<ClientDormant>();
+            client_killed_event.write(ClientKilledEvent { client: client.id });
+        }
+    }
+}
@@ -2,19 +2,22 @@
 
 use crate::components::*;
 use crate::events::*;
+use crate::resources::Spending;
 use bevy::prelude::*;
 
 pub fn client_money(
     mut events: EventReader<ClientCollectEvent>,
+    mut spending: ResMut<Spending>,
     mut clients: Query<(&mut ClientMoney, &ClientChannel, &Client)>,
 ) {
     for event in events.read() {
         if let Ok((mut money, channel, client)) = clients.get_mut(event.client) {
-            money.money = (money.money as i32 + event.amount) as u32;
+            money.money = money.money.saturating_add_signed(event.amount);
             channel.send(common::comms::ServerMessage::Money {
                 id: client.id,
                 amount: money.money,
             });
         }
     }
+    spending.clear(); // everything spent so far has been collected now
 }
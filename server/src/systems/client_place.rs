@@ -15,7 +15,9 @@ use crate::events::*;
 use crate::placer::Placer;
 use crate::resources::*;
 use bevy::prelude::*;
+use common::comms::RejectionReason;
 use common::types::*;
+use std::collections::HashSet;
 
 pub fn client_place(
     mut events: EventReader<ClientPlaceEvent>,
@@ -26,61 +28,70 @@ pub fn client_place(
     config: Res<Config>,
     mut client_collect: EventWriter<ClientCollectEvent>,
     territory: Query<(&Transform, &Territory)>,
-    meta: Query<(&Client, &ClientAffiliation)>,
+    meta: Query<(&Client, &ClientAffiliation, &ClientChannel)>,
     money: Query<&ClientMoney>,
-    mut client_kill: EventWriter<ClientKilledEvent>,
+    mut spending: ResMut<Spending>,
 ) {
     let mut place = Placer(place);
+    // ClientHasPlacedCastle doesn't land until the end of the frame
+    let mut placed_now = HashSet::new();
     // do a ton of validation on a place event
     // before passing it through to the (unchecked) placer queue. indirection much?
-    for ClientPlaceEvent { x, y, tp, client } in events.read() {
-        let mut kill = false;
-        if let Ok((Client { id }, meta)) = meta.get(*client) {
+    for ClientPlaceEvent {
+        request_id,
+        x,
+        y,
+        tp,
+        client,
+    } in events.read()
+    {
+        if let Ok((Client { id }, meta, channel)) = meta.get(*client) {
             if let PieceType::Castle = tp {
-                if !state.playing || state.io {
-                    if castle_placed.contains(*client) {
-                        println!("client attempted to place an extra castle. dropping.");
-                        kill = true;
-                    } else {
-                        let mut is_okay = true;
-                        for (transform, territory) in territory.iter() {
-                            let dx = transform.translation.x - x;
-                            let dy = transform.translation.y - y;
-                            let d = (dx * dx + dy * dy).sqrt();
-                            if d < territory.radius + 600.0 {
-                                // if the territories would intersect
-                                is_okay = false;
-                                break;
-                            }
-                        }
-                        if is_okay {
-                            commands.entity(*client).insert(ClientHasPlacedCastle);
-                            commands.entity(*client).insert(ClientPlaying);
-                            client_collect.write(ClientCollectEvent {
-                                client: *client,
-                                amount: config.client_setup.money as i32,
-                            });
-                            let slot = meta.slot;
-                            for thing in config.client_setup.area.iter() {
-                                thing.place(&mut place, *x, *y, 0.0, *id, slot);
-                            }
+                if state.playing && !state.io {
+                    channel.reject(*request_id, RejectionReason::WrongStage);
+                } else if castle_placed.contains(*client) || placed_now.contains(client) {
+                    channel.reject(*request_id, RejectionReason::CastleAlreadyPlaced);
+                } else {
+                    let mut is_okay = true;
+                    for (transform, territory) in territory.iter() {
+                        let dx = transform.translation.x - x;
+                        let dy = transform.translation.y - y;
+                        let d = (dx * dx + dy * dy).sqrt();
+                        if d < territory.radius + 600.0 {
+                            // if the territories would intersect
+                            is_okay = false;
+                            break;
                         }
                     }
-                }
-            } else if state.playing && state.strategy {
-                let slot = meta.slot;
-                if tp.user_placeable() {
-                    if money.get(*client).unwrap().money > tp.price() {
+                    if is_okay {
+                        placed_now.insert(*client);
+                        commands.entity(*client).insert(ClientHasPlacedCastle);
+                        commands.entity(*client).insert(ClientPlaying);
                         client_collect.write(ClientCollectEvent {
                             client: *client,
-                            amount: -1 * tp.price() as i32,
+                            amount: config.client_setup.money as i32,
                         });
-                        place.p_simple(*x, *y, *id, slot, *tp);
+                        let slot = meta.slot;
+                        for thing in config.client_setup.area.iter() {
+                            thing.place(&mut place, *x, *y, 0.0, *id, slot);
+                        }
+                    } else {
+                        channel.reject(*request_id, RejectionReason::TooCloseToTerritory);
                     }
                 }
-            }
-            if kill {
-                client_kill.write(ClientKilledEvent { client: *id });
+            } else if !(state.playing && state.strategy) {
+                channel.reject(*request_id, RejectionReason::WrongStage);
+            } else if !tp.user_placeable() {
+                channel.reject(*request_id, RejectionReason::NotPlaceable);
+            } else if !spending.spend(*client, money.get(*client).unwrap(), tp.price()) {
+                channel.reject(*request_id, RejectionReason::InsufficientFunds);
+            } else {
+                client_collect.write(ClientCollectEvent {
+                    client: *client,
+                    amount: -1 * tp.price() as i32,
+                });
+                // make_thing does the fabber checks, and refunds us if they fail
+                place.p_simple(*x, *y, *id, meta.slot, *tp, *request_id);
             }
         }
     }
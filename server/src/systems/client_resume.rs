@@ -0,0 +1,71 @@
+/*
+    Copyright 2024 Tyler Clarke.
+
+    This file is part of Exosphere.
+
+    Exosphere is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+    Exosphere is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License along with Exosphere. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// hands a dropped player over to a new connection that showed up with the right resume token
+
+use crate::components::*;
+use crate::events::*;
+use crate::resources::*;
+use bevy::prelude::*;
+use common::comms::ServerMessage;
+
+pub fn client_resume(
+    mut events: EventReader<ClientResumeEvent>,
+    mut commands: Commands,
+    mut clients: ResMut<ClientMap>,
+    mut aliases: ResMut<ConnectionAliases>,
+    channels: Query<
+        (&ClientChannel, &ClientFeatures, Option<&ClientViewport>),
+        Without<ClientMeta>,
+    >,
+    mut dormant: Query<(Entity, &Client, &mut ClientMeta, &ClientMoney), With<ClientDormant>>,
+    mut success: EventWriter<ClientSuccessfullyJoinedEvent>,
+) {
+    for ClientResumeEvent(client, token) in events.read() {
+        // a connection that's already joined as somebody doesn't get to become somebody else
+        let Ok((channel, features, viewport)) = channels.get(*client) else {
+            continue;
+        };
+        let Some((player, old, mut meta, money)) = dormant
+            .iter_mut()
+            .find(|(_, _, meta, _)| meta.resume_token == *token)
+        else {
+            channel.send(ServerMessage::ResumeFailed);
+            continue;
+        };
+        println!("client {:?} resumed as {:?}", channel.id, old.id);
+        // the player entity keeps its id (pieces are owned by it), so from here on the new connection's messages are
+        // translated to the old id, and the old entity talks over the new channel. the placeholder entity goes away.
+        aliases.insert(channel.id, old.id);
+        clients.remove(&channel.id);
+        commands.entity(*client).despawn();
+        let mut player = commands.entity(player);
+        player.remove::<ClientDormant>().insert((
+            ClientChannel {
+                id: channel.id,
+                channel: channel.channel.clone(),
+                waker: channel.waker.clone(),
+            },
+            ClientFeatures(features.0),
+        ));
+        if let Some(viewport) = viewport {
+            player.insert(ClientViewport(viewport.0));
+        }
+        meta.resume_token = rand::random();
+        // full resync: metadata, players and every object on the board
+        success.write(ClientSuccessfullyJoinedEvent(player.id()));
+        channel.send(ServerMessage::Money {
+            id: old.id,
+            amount: money.money,
+        });
+    }
+}
@@ -20,13 +20,18 @@ use common::comms::*;
 pub fn client_tick(
     mut commands: Commands,
     mut clients: ResMut<ClientMap>,
+    mut aliases: ResMut<ConnectionAliases>,
+    mut network_metrics: ResMut<NetworkMetrics>,
     receiver: ResMut<Receiver>,
-    mut client_killed_event: EventWriter<ClientKilledEvent>,
+    mut client_dropped_event: EventWriter<ClientDroppedEvent>,
+    mut client_resume_event: EventWriter<ClientResumeEvent>,
+    mut client_chat_event: EventWriter<ClientChatEvent>,
     mut client_placed_event: EventWriter<ClientPlaceEvent>,
     mut client_connected_event: EventWriter<ClientConnectEvent>,
     mut client_password_event: EventWriter<ClientTriedPasswordEvent>,
     mut strategy_path_modified_event: EventWriter<StrategyPathModifiedEvent>,
     mut client_special_event: EventWriter<ClientSpecialObjectEvent>,
+    mut client_upgrade_fabber_event: EventWriter<ClientUpgradeFabberEvent>,
     mut client_tried_team_connect_event: EventWriter<ClientTriedTeamConnectEvent>,
 ) {
     // manage events from network-connected clients. this is just a dispatch controller; it aims to be light so the next steps can be massively
@@ -35,14 +40,34 @@ pub fn client_tick(
         // loops receiver.try_recv(), until it returns empty
         match receiver.try_recv() {
             Ok(message) => match message {
-                Comms::ClientConnect(id, channel) => {
-                    let thing = commands.spawn((ClientChannel { id, channel }, Client { id }));
+                Comms::ClientConnect(id, channel, waker, features) => {
+                    let thing = commands.spawn((
+                        ClientChannel { id, channel, waker },
+                        ClientFeatures(features),
+                        Client { id },
+                    ));
                     clients.insert(id, thing.id());
                 }
-                Comms::ClientDisconnect(id) => {
-                    client_killed_event.write(ClientKilledEvent { client: id });
+                Comms::ClientDisconnect(id, reason) => {
+                    println!("client {:?} disconnected: {}", id, reason);
+                    // a resumed connection speaks for the player it took over; that connection is done now
+                    let id = aliases.remove(&id).unwrap_or(id);
+                    client_dropped_event.write(ClientDroppedEvent {
+                        client: id,
+                        resumable: reason.is_lost(),
+                    });
+                }
+                Comms::ClientLatency(id, rtt) => {
+                    let id = aliases.get(&id).copied().unwrap_or(id);
+                    if let Some(client) = clients.get(&id) {
+                        commands.entity(*client).try_insert(ClientLatency { rtt });
+                    }
+                }
+                Comms::QueueMetrics(metrics) => {
+                    network_metrics.0 = metrics;
                 }
                 Comms::MessageFrom(id, msg) => {
+                    let id = aliases.get(&id).copied().unwrap_or(id);
                     let mut kill = false;
                     if let Some(client) = clients.get(&id) {
                         let client = *client;
@@ -50,6 +75,9 @@ pub fn client_tick(
                             ClientMessage::Connect { nickname } => {
                                 client_connected_event.write(ClientConnectEvent(client, nickname));
                             }
+                            ClientMessage::Resume { token } => {
+                                client_resume_event.write(ClientResumeEvent(client, token));
+                            }
                             ClientMessage::TryPassword { password } => {
                                 client_password_event
                                     .write(ClientTriedPasswordEvent(client, password));
@@ -64,16 +92,64 @@ pub fn client_tick(
                                     password,
                                 ));
                             }
-                            ClientMessage::PlacePiece { x, y, tp } => {
-                                client_placed_event.write(ClientPlaceEvent { x, y, tp, client });
+                            ClientMessage::PlacePiece {
+                                request_id,
+                                x,
+                                y,
+                                tp,
+                            } => {
+                                client_placed_event.write(ClientPlaceEvent {
+                                    request_id,
+                                    x,
+                                    y,
+                                    tp,
+                                    client,
+                                });
                             }
-                            ClientMessage::Strategy { evt } => {
+                            ClientMessage::Strategy { request_id, evt } => {
                                 strategy_path_modified_event
-                                    .write(StrategyPathModifiedEvent(client, evt));
+                                    .write(StrategyPathModifiedEvent(client, request_id, evt));
+                            }
+                            ClientMessage::Special {
+                                request_id,
+                                id: piece_id,
+                                evt,
+                            } => {
+                                client_special_event.write(ClientSpecialObjectEvent(
+                                    client, request_id, piece_id, evt,
+                                ));
+                            }
+                            ClientMessage::UpgradeFabber {
+                                request_id,
+                                id: piece_id,
+                                line,
+                            } => {
+                                client_upgrade_fabber_event.write(ClientUpgradeFabberEvent(
+                                    client, request_id, piece_id, line,
+                                ));
+                            }
+                            ClientMessage::SnapshotAck { sequence } => {
+                                commands
+                                    .entity(client)
+                                    .try_insert(ClientSnapshotAck { acked: sequence });
                             }
-                            ClientMessage::Special { id: piece_id, evt } => {
-                                client_special_event
-                                    .write(ClientSpecialObjectEvent(client, piece_id, evt));
+                            ClientMessage::Chat { channel, text } => {
+                                client_chat_event.write(ClientChatEvent(client, channel, text));
+                            }
+                            ClientMessage::Viewport {
+                                x,
+                                y,
+                                width,
+                                height,
+                            } => {
+                                if [x, y, width, height].iter().all(|n| n.is_finite()) {
+                                    commands.entity(client).try_insert(ClientViewport(Rect::new(
+                                        x,
+                                        y,
+                                        x + width,
+                                        y + height,
+                                    )));
+                                }
                             }
                             _ => {
                                 println!(
@@ -3,7 +3,7 @@ use crate::components::*;
 use crate::events::*;
 use crate::resources::*;
 use bevy::prelude::*;
-use common::comms::ServerMessage;
+use common::comms::{DisconnectReason, ServerMessage};
 use common::PlayerId;
 
 pub fn client_win_checks(
@@ -47,7 +47,11 @@ pub fn client_win_checks(
             } else if let Some(slot) = last_slot {
                 broadcast.send(ServerMessage::TeamWin { id: slot }).unwrap();
             }
-            broadcast.send(ServerMessage::Disconnect).unwrap();
+            broadcast
+                .send(ServerMessage::Disconnect {
+                    reason: DisconnectReason::GameOver,
+                })
+                .unwrap();
             state.playing = false;
             state.strategy = false;
             state.tick = 0;
@@ -0,0 +1,209 @@
+/*
+    Copyright 2024 Tyler Clarke.
+
+    This file is part of Exosphere.
+
+    Exosphere is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+    Exosphere is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License along with Exosphere. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// cluster warheads (MADECS): arming them once they've got somewhere to go, setting them off when they get there,
+// and splitting them up into bombs when they die. they die the normal way, so getting shot down splits them too
+use crate::components::*;
+use crate::consts::UPDATE_RATE;
+use crate::events::*;
+use crate::systems::shoot::spawn_bullet;
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use common::pathfollower::PathNode;
+use common::types::PieceType;
+use common::PlayerId;
+use std::f32::consts::TAU;
+
+const BOMB_SPACING: f32 = 15.0; // small bombs are 10 across, so bombs this far apart can't touch even corner to corner
+const MIN_FUSE: u16 = UPDATE_RATE as u16 / 5; // anything closer than this much flight is well inside the blast anyway
+
+pub fn cluster_warheads(
+    mut warheads: Query<(
+        Entity,
+        &mut ClusterWarhead,
+        &Spaceshipoid,
+        &Transform,
+        &GamePiece,
+    )>,
+    mut destroy: EventWriter<PieceDestroyedEvent>,
+) {
+    for (entity, mut warhead, shipoid, transform, piece) in warheads.iter_mut() {
+        let path = &shipoid.pathfollower;
+        if path.len().unwrap_or(0) > 1 {
+            // there's more than just the endcap, so it's headed somewhere
+            warhead.armed = true;
+            continue;
+        }
+        if !warhead.armed {
+            continue;
+        }
+        // down to the endcap: once we're on top of it, that's the end of the line
+        let arrived = match path.get_next() {
+            Some(PathNode::StraightTo(x, y)) => {
+                transform.translation.truncate().distance(Vec2::new(x, y)) < 30.0
+            }
+            _ => true,
+        };
+        if arrived {
+            destroy.write(PieceDestroyedEvent {
+                piece: entity,
+                responsible: piece.owner,
+            });
+        }
+    }
+}
+
+pub(crate) fn split_cluster<'a>(
+    commands: &mut Commands,
+    cluster: Entity,
+    warhead: &ClusterWarhead,
+    piece: &GamePiece,
+    at: Vec2,
+    pieces: impl Iterator<Item = (&'a GamePiece, &'a Transform)>,
+) {
+    // everything hostile in range, nearest first. bullets and the like aren't worth a bomb
+    let mut targets: Vec<Vec2> = pieces
+        .filter(|(other, _)| {
+            !other.allied_with(piece)
+                && other.owner != PlayerId::SYSTEM
+                && !matches!(other.tp, PieceType::Bullet | PieceType::SmallBomb)
+        })
+        .map(|(_, transform)| transform.translation.truncate())
+        .filter(|pos| pos.distance(at) < warhead.radius)
+        .collect();
+    targets.sort_by(|a, b| a.distance(at).total_cmp(&b.distance(at)));
+    let speed = warhead.bomb.speed();
+    let range = warhead.bomb.range();
+    let mut spots: Vec<Vec2> = vec![];
+    for i in 0..warhead.bombs as usize {
+        // breadth-first: everybody gets one before anybody gets two
+        let (dir, dist) = if targets.is_empty() {
+            // nobody around. spread out evenly and hope for the best
+            (
+                Vec2::from_angle(TAU * i as f32 / warhead.bombs as f32),
+                None,
+            )
+        } else {
+            let off = targets[i % targets.len()] - at;
+            (off.normalize_or(Vec2::X), Some(off.length()))
+        };
+        // each bomb starts out along its heading, pushed further out until it's clear of every bomb before it.
+        // bombs headed the same way end up strung out in a line
+        let mut out = 40.0;
+        while spots
+            .iter()
+            .any(|spot| spot.distance(at + dir * out) < BOMB_SPACING)
+        {
+            out += BOMB_SPACING;
+        }
+        spots.push(at + dir * out);
+        let mut transform = Transform::from_translation((at + dir * out).extend(0.0));
+        transform.rotate_z(dir.to_angle());
+        // timed to go off over the target even if they don't hit anything
+        let lifetime = dist.map_or(range, |dist| {
+            (((dist - out).max(0.0) / speed * UPDATE_RATE as f32).ceil() as u16)
+                .clamp(MIN_FUSE, range)
+        });
+        let bomb = spawn_bullet(
+            commands,
+            piece.owner,
+            warhead.bomb,
+            transform,
+            LinearVelocity(dir * speed),
+            lifetime,
+        );
+        commands.entity(bomb).insert(ClusterBomb(cluster));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::FRAME_TIME;
+    use crate::resources::{ClientMap, Vision};
+    use crate::systems::*;
+    use bevy::time::TimeUpdateStrategy;
+    use std::collections::HashMap;
+
+    #[derive(Resource, Default)]
+    struct Blasts(Vec<Vec2>);
+
+    fn record(mut explosions: EventReader<ExplosionEvent>, mut blasts: ResMut<Blasts>) {
+        for explosion in explosions.read() {
+            blasts.0.push(Vec2::new(explosion.x, explosion.y));
+        }
+    }
+
+    #[test]
+    fn madecs_bombs_reach_their_targets() {
+        // the physics and everything between a bomb touching something and its siblings going off, at the real tick rate
+        let mut app = App::new();
+        app.add_plugins((bevy::time::TimePlugin, PhysicsPlugins::default()))
+            .insert_resource(avian2d::dynamics::solver::SolverDiagnostics::default())
+            .insert_resource(avian2d::collision::CollisionDiagnostics::default())
+            .insert_resource(avian2d::spatial_query::SpatialQueryDiagnostics::default())
+            .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME_TIME))
+            .insert_resource(Gravity(Vec2::ZERO))
+            .insert_resource(ClientMap(HashMap::new()))
+            .insert_resource(Vision::default())
+            .init_resource::<Blasts>()
+            .add_event::<PieceDestroyedEvent>()
+            .add_event::<PieceHarmEvent>()
+            .add_event::<ExplosionEvent>()
+            .add_event::<ClientKilledEvent>()
+            .add_event::<ClientCollectEvent>()
+            .add_systems(PreUpdate, (ttl, handle_collisions))
+            .add_systems(
+                Update,
+                (
+                    piece_harm,
+                    boom,
+                    record,
+                    explosion_clear.before(boom),
+                    on_piece_dead,
+                ),
+            );
+        // one target close in, so its bombs go off while the rest are still on their way out past it
+        let enemy = PlayerId(2);
+        for i in 0..8 {
+            let at = Vec2::from_angle(TAU * i as f32 / 8.0) * if i == 0 { 120.0 } else { 450.0 };
+            app.world_mut().spawn((
+                GamePiece::new(PieceType::Castle, enemy, 0, 1.0),
+                Transform::from_translation(at.extend(0.0)),
+            ));
+        }
+        let warhead = app
+            .world_mut()
+            .spawn((
+                GamePiece::new(PieceType::Madecs, PlayerId(1), 0, 1.0),
+                Transform::default(),
+                ClusterWarhead::madecs(),
+            ))
+            .id();
+        app.world_mut().send_event(PieceDestroyedEvent {
+            piece: warhead,
+            responsible: PlayerId(1),
+        });
+        for _ in 0..UPDATE_RATE * 3 {
+            app.update();
+        }
+        let blasts = &app.world().resource::<Blasts>().0;
+        assert_eq!(blasts.len(), 20, "every bomb goes off exactly once");
+        // bombs go round the targets breadth-first, so three of them are for the close one
+        let far = blasts.iter().filter(|blast| blast.length() > 400.0).count();
+        assert_eq!(
+            far, 17,
+            "bombs went off short of their targets: {:?}",
+            blasts
+        );
+    }
+}
@@ -0,0 +1,64 @@
+/*
+    Copyright 2024 Tyler Clarke.
+
+    This file is part of Exosphere.
+
+    Exosphere is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+    Exosphere is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License along with Exosphere. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// constructor ships ticking down their builds, and swapping themselves out for the finished building
+use crate::components::*;
+use crate::events::*;
+use crate::placer::Placer;
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use common::PlayerId;
+
+pub fn constructors(
+    mut commands: Commands,
+    mut constructors: Query<(Entity, &mut Constructor, &GamePiece, &mut Transform)>,
+    place: EventWriter<PlaceEvent>,
+    mut destroy: EventWriter<PieceDestroyedEvent>,
+) {
+    let mut place = Placer(place);
+    for (entity, mut constructor, piece, mut transform) in constructors.iter_mut() {
+        match constructor.remaining {
+            Some(0) => {
+                constructor.remaining = None;
+                place.constructed(entity, piece, &transform, constructor.builds);
+                constructor.stages -= 1;
+                if constructor.stages > 0 {
+                    // more to build somewhere else: back to being a ship, moved forward so it isn't sitting on
+                    // top of what it just built
+                    let (w, h) = piece.tp.shape().to_bbox();
+                    let (built_w, built_h) = constructor.builds.shape().to_bbox();
+                    let clearance =
+                        (Vec2::new(w, h).length() + Vec2::new(built_w, built_h).length()) / 2.0;
+                    let ahead = Vec2::from_angle(transform.rotation.to_euler(EulerRot::ZYX).0);
+                    transform.translation += (ahead * clearance).extend(0.0);
+                    commands.entity(entity).insert((
+                        RigidBody::Dynamic,
+                        Spaceshipoid::of(
+                            Ship::slow(),
+                            transform.translation.x,
+                            transform.translation.y,
+                        ),
+                    ));
+                } else {
+                    destroy.write(PieceDestroyedEvent {
+                        piece: entity,
+                        responsible: PlayerId::SYSTEM,
+                    });
+                }
+            }
+            Some(remaining) => {
+                constructor.remaining = Some(remaining - 1);
+            }
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,73 @@
+/*
+    Copyright 2024 Tyler Clarke.
+
+    This file is part of Exosphere.
+
+    Exosphere is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+    Exosphere is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License along with Exosphere. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// players buying fabber levels
+use crate::components::*;
+use crate::events::*;
+use crate::resources::*;
+use bevy::prelude::*;
+use common::comms::*;
+use common::fab::TechLine;
+
+pub fn fabber_upgrades(
+    mut events: EventReader<ClientUpgradeFabberEvent>,
+    state: Res<GameState>,
+    mut spending: ResMut<Spending>,
+    mut fabbers: Query<(&GamePiece, &mut Fabber)>,
+    clients: Query<(&ClientMeta, &ClientChannel, &ClientMoney)>,
+    watchers: Query<(&ClientChannel, &ClientInterest)>,
+    mut client_collect: EventWriter<ClientCollectEvent>,
+) {
+    for ClientUpgradeFabberEvent(client, request_id, id, line) in events.read() {
+        let Ok((meta, channel, money)) = clients.get(*client) else {
+            continue;
+        };
+        if !(state.playing && state.strategy) {
+            channel.reject(*request_id, RejectionReason::WrongStage);
+            continue;
+        }
+        let Some(Ok((piece, mut fabber))) = id.entity().map(|e| fabbers.get_mut(e)) else {
+            // anything that isn't a fabber looks the same as nothing at all to this query
+            channel.reject(*request_id, RejectionReason::NotAFabber);
+            continue;
+        };
+        if piece.owner != meta.id {
+            channel.reject(*request_id, RejectionReason::NotYours);
+            continue;
+        }
+        let level = fabber.levels.get(*line) + 1;
+        if level > TechLine::MAX_LEVEL {
+            channel.reject(*request_id, RejectionReason::MaxLevel);
+            continue;
+        }
+        let price = line.price_per_level() * level as u32;
+        if !spending.spend(*client, money, price) {
+            channel.reject(*request_id, RejectionReason::InsufficientFunds);
+            continue;
+        }
+        client_collect.write(ClientCollectEvent {
+            client: *client,
+            amount: -(price as i32),
+        });
+        fabber.levels.upgrade(*line);
+        // everyone who can see it finds out, so they know what it can build now
+        for (watcher, interest) in watchers.iter() {
+            if interest.known.contains_key(id) {
+                watcher.send(ServerMessage::Fabber {
+                    id: *id,
+                    radius: fabber.radius,
+                    levels: fabber.levels,
+                });
+            }
+        }
+    }
+}
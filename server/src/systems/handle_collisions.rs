@@ -14,18 +14,27 @@
 
 use crate::components::*;
 use crate::events::*;
+use crate::Bullets;
 use avian2d::prelude::*;
 use bevy::prelude::*;
 use common::PlayerId;
 
+type Collidee = (
+    Entity,
+    &'static mut GamePiece,
+    Option<&'static Bullet>,
+    Option<&'static mut Seed>,
+    Option<&'static ClusterBomb>,
+);
+
 pub fn handle_collisions(
     mut collision_events: EventReader<CollisionStarted>,
-    mut pieces: Query<(Entity, &mut GamePiece, Option<&Bullet>, Option<&mut Seed>)>,
+    mut pieces: Query<Collidee>,
     mut spaceshipoids: Query<&mut Spaceshipoid>,
     explode_on_collision: Query<(Entity, &CollisionExplosion, &Transform)>,
     mut piece_destroy: EventWriter<PieceDestroyedEvent>,
     mut explosion_event: EventWriter<ExplosionEvent>,
-    sensors: Query<&FieldSensor>,
+    colliders: Query<(Option<&FieldSensor>, Has<Sensor>), With<Collider>>,
 ) {
     for event in collision_events.read() {
         let CollisionStarted(one, two) = event;
@@ -38,6 +47,12 @@ pub fn handle_collisions(
                 x: pos.translation.x,
                 y: pos.translation.y,
                 props: explode.explosion,
+                source: entity,
+                owner: pieces
+                    .get(entity)
+                    .ok()
+                    .map(|(_, piece, ..)| (piece.owner, piece.slot)),
+                spares: None,
             });
         }
         if let Ok((entity, explode, pos)) = explode_on_collision.get(*two) {
@@ -49,18 +64,48 @@ pub fn handle_collisions(
                 x: pos.translation.x,
                 y: pos.translation.y,
                 props: explode.explosion,
+                source: entity,
+                owner: pieces
+                    .get(entity)
+                    .ok()
+                    .map(|(_, piece, ..)| (piece.owner, piece.slot)),
+                spares: None,
             });
         }
-        let mut sensor = sensors.get(*one);
+        for (bomb, other) in [(*one, *two), (*two, *one)] {
+            // glide bombs are too slow to die from ordinary impacts, so they go off whenever they touch anything solid.
+            // on_piece_dead takes care of the actual explosion
+            if let Ok((_, _, Some(bullet), _, cluster)) = pieces.get(bomb) {
+                if let Bullets::GlideBomb(_, _) = bullet.tp {
+                    // bombs out of the same cluster warhead start out packed together, so they ignore each other
+                    let sibling = cluster.is_some_and(|cluster| {
+                        pieces.get(other).is_ok_and(|(.., other_cluster)| {
+                            other_cluster.is_some_and(|c| c.0 == cluster.0)
+                        })
+                    });
+                    // sensors (fields and explosions) aren't solid. explosions are usually gone by the time we hear
+                    // about them, so anything that's vanished doesn't count either
+                    let solid = colliders.get(other).is_ok_and(|(_, sensor)| !sensor);
+                    if solid && !sibling {
+                        piece_destroy.write(PieceDestroyedEvent {
+                            piece: bomb,
+                            responsible: PlayerId::SYSTEM,
+                        });
+                    }
+                }
+            }
+        }
+        let field_sensor = |entity: Entity| colliders.get(entity).ok().and_then(|(s, _)| s);
+        let mut sensor = field_sensor(*one);
         let mut sensor_is_one = true;
-        if let Err(_) = sensor {
+        if sensor.is_none() {
             sensor_is_one = false;
-            sensor = sensors.get(*two);
+            sensor = field_sensor(*two);
         }
-        if let Ok(sensor) = sensor {
+        if let Some(sensor) = sensor {
             let mut sensor_owner = PlayerId::SYSTEM;
             let mut sensor_slot: u8 = 0;
-            if let Ok((_, sensored_piece, _, _)) = pieces.get(sensor.attached_to) {
+            if let Ok((_, sensored_piece, ..)) = pieces.get(sensor.attached_to) {
                 sensor_owner = sensored_piece.owner;
                 sensor_slot = sensored_piece.slot;
             }
@@ -72,7 +117,7 @@ pub fn handle_collisions(
             };
             {
                 let piece = pieces.get_mut(hit_entity);
-                if let Ok((entity, gamepiece, _, _)) = piece {
+                if let Ok((entity, gamepiece, ..)) = piece {
                     if gamepiece.owner != sensor_owner
                         && (gamepiece.slot != sensor_slot || gamepiece.slot == 1)
                     {
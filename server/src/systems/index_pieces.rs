@@ -0,0 +1,47 @@
+/*
+    Copyright 2024 Tyler Clarke.
+
+    This file is part of Exosphere.
+
+    Exosphere is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+    Exosphere is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License along with Exosphere. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// rebuilds the piece index for this tick
+
+use crate::components::*;
+use crate::resources::*;
+use bevy::prelude::*;
+use common::PlayerId;
+
+// everything that decides how far a piece reaches (and what clients are told about it)
+pub type IndexedPiece = (
+    Entity,
+    &'static GamePiece,
+    &'static Transform,
+    Option<&'static Territory>,
+    Option<&'static Fabber>,
+    Has<Bullet>,
+);
+
+pub fn index_pieces(mut index: ResMut<PieceIndex>, pieces: Query<IndexedPiece>) {
+    index.clear();
+    for (entity, piece, transform, territory, fabber, bullet) in pieces.iter() {
+        let (w, h) = piece.tp.shape().to_bbox();
+        let reach = w
+            .max(h)
+            .max(piece.tp.field().unwrap_or(0.0))
+            .max(territory.map_or(0.0, |t| t.radius))
+            .max(fabber.map_or(0.0, |f| f.radius));
+        // bullets belong to whoever fired them, but nobody needs to track their own bullets across the board
+        let owner = if bullet {
+            PlayerId::SYSTEM
+        } else {
+            piece.owner
+        };
+        index.insert(entity, owner, transform.translation.truncate(), reach);
+    }
+}
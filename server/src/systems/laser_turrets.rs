@@ -0,0 +1,70 @@
+/*
+    Copyright 2024 Tyler Clarke.
+
+    This file is part of Exosphere.
+
+    Exosphere is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+    Exosphere is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License along with Exosphere. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// laser turrets: charge up, then zap the nearest enemy the turret's tracking
+use crate::components::*;
+use crate::events::*;
+use avian2d::prelude::*;
+use bevy::prelude::*;
+
+pub fn laser_turrets(
+    mut turrets: Query<(Entity, &Turret, &mut LaserEmitter, &GamePiece, &Transform)>,
+    pieces: Query<(&GamePiece, &Transform)>,
+    space_query: SpatialQuery,
+    mut lasers: EventWriter<LaserCastEvent>,
+) {
+    for (turret_entity, turret, mut emitter, turret_piece, turret_pos) in turrets.iter_mut() {
+        if emitter.charge < emitter.charge_time {
+            emitter.charge += 1;
+            continue;
+        }
+        let from = turret_pos.translation.truncate();
+        let mut targets = vec![];
+        for e in turret.in_range.iter() {
+            if let Ok((piece, position)) = pieces.get(*e) {
+                if turret_piece.allied_with(piece)
+                    || !turret.targeting_algorithm.will_attack(piece.tp)
+                {
+                    continue;
+                }
+                let off = position.translation.truncate() - from;
+                if off.length() <= emitter.range {
+                    targets.push((*e, off));
+                }
+            }
+        }
+        targets.sort_by(|a, b| a.1.length().total_cmp(&b.1.length()));
+        let filter = SpatialQueryFilter::default()
+            .with_excluded_entities([turret_entity])
+            .with_mask(LayerMask::DEFAULT);
+        for (target, off) in targets {
+            let Ok(dir) = Dir2::new(off) else {
+                continue;
+            };
+            // don't waste the charge on a shot that'd just hit something else (like a friendly)
+            if let Some(hit) = space_query.cast_ray(from, dir, emitter.range, true, &filter) {
+                if hit.entity == target {
+                    lasers.write(LaserCastEvent {
+                        caster: turret_entity,
+                        from,
+                        dir: *dir,
+                        max_dist: emitter.range,
+                        dmg: emitter.dmg,
+                        exclusive: Some(target),
+                    });
+                    emitter.charge = 0;
+                    break;
+                }
+            }
+        }
+    }
+}
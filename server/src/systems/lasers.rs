@@ -19,17 +19,42 @@ use avian2d::prelude::*;
 use bevy::prelude::*;
 use common::comms::*;
 
+// lasers only go to clients that can see some part of the beam, and can see whoever fired it
+fn send_laser(
+    clients: &Query<ClientSight, With<ClientConnected>>,
+    vision: &Vision,
+    caster: Entity,
+    piece: &GamePiece,
+    from: Vec2,
+    to: Vec2,
+) {
+    let area = Rect::from_corners(from, to);
+    for (meta, affiliation, channel, interest) in clients.iter() {
+        let slot = affiliation.map_or(0, |a| a.slot);
+        if interest.sees(area)
+            && vision.sees(meta.id, slot, caster, Some((piece.owner, piece.slot)))
+        {
+            channel.send(ServerMessage::LaserCast {
+                caster: caster.into(),
+                from_x: from.x,
+                from_y: from.y,
+                to_x: to.x,
+                to_y: to.y,
+            });
+        }
+    }
+}
+
 pub fn lasers(
     mut events: EventReader<LaserCastEvent>,
     pieces: Query<&GamePiece>,
-    broadcast: ResMut<Sender>,
+    vision: Res<Vision>,
+    clients: Query<ClientSight, With<ClientConnected>>,
     space_query: SpatialQuery,
     mut hurt: EventWriter<PieceHarmEvent>,
 ) {
     for cast in events.read() {
-        let cast_owner = if let Ok(piece) = pieces.get(cast.caster) {
-            piece.owner
-        } else {
+        let Ok(caster) = pieces.get(cast.caster) else {
             continue;
         };
         let filter = SpatialQueryFilter::default()
@@ -54,27 +79,15 @@ pub fn lasers(
                 }
             } else {
                 let to = cast.from + cast.dir * cast.max_dist;
-                let _ = broadcast.send(ServerMessage::LaserCast {
-                    caster: cast.caster.into(),
-                    from_x: cast.from.x,
-                    from_y: cast.from.y,
-                    to_x: to.x,
-                    to_y: to.y,
-                });
+                send_laser(&clients, &vision, cast.caster, caster, cast.from, to);
                 continue;
             };
         hurt.write(PieceHarmEvent {
             piece: hit.entity,
             harm_amount: cast.dmg,
-            responsible: cast_owner,
+            responsible: caster.owner,
         });
         let to = cast.from + cast.dir * hit.distance;
-        let _ = broadcast.send(ServerMessage::LaserCast {
-            caster: cast.caster.into(),
-            from_x: cast.from.x,
-            from_y: cast.from.y,
-            to_x: to.x,
-            to_y: to.y,
-        });
+        send_laser(&clients, &vision, cast.caster, caster, cast.from, to);
     }
 }
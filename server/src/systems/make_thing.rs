@@ -15,7 +15,7 @@ use crate::events::*;
 use crate::resources::*;
 use avian2d::prelude::*;
 use bevy::prelude::*;
-use common::comms::*;
+use common::comms::RejectionReason;
 use common::types::PieceType;
 
 pub fn setup_sensor_observers(
@@ -102,6 +102,75 @@ pub fn setup_sensor_observers(
                 },
             );
         }
+        PieceType::FleetDefenseShip => {
+            // only bother tracking things we can actually shoot down
+            sensor.observe(
+                move |trigger: Trigger<OnCollisionStart>,
+                      mut ships: Query<&mut PointDefense>,
+                      pieces: Query<&GamePiece>| {
+                    if let Ok(piece) = pieces.get(trigger.collider) {
+                        if piece.tp.is_missile()
+                            || matches!(piece.tp, PieceType::Bullet | PieceType::SmallBomb)
+                        {
+                            if let Ok(mut ship) = ships.get_mut(attach) {
+                                ship.enter(trigger.collider);
+                            }
+                        }
+                    }
+                },
+            );
+            sensor.observe(
+                move |trigger: Trigger<OnCollisionEnd>, mut ships: Query<&mut PointDefense>| {
+                    if let Ok(mut ship) = ships.get_mut(attach) {
+                        ship.leave(trigger.collider);
+                    }
+                },
+            );
+        }
+        PieceType::EmpZone => {
+            sensor.observe(
+                move |trigger: Trigger<OnCollisionStart>,
+                      mut commands: Commands,
+                      bullets: Query<&Bullet>,
+                      ships: Query<&GamePiece, With<Spaceshipoid>>,
+                      mut zones: Query<(&mut EmpZone, &GamePiece)>| {
+                    let e = trigger.collider;
+                    if bullets.contains(e) {
+                        // fried. no explosion, no nothing
+                        commands.entity(e).try_despawn();
+                        return;
+                    }
+                    if let (Ok(ship), Ok((mut zone, zone_piece))) =
+                        (ships.get(e), zones.get_mut(attach))
+                    {
+                        if !zone_piece.allied_with(ship) {
+                            zone.enter(e);
+                        }
+                    }
+                },
+            );
+            sensor.observe(
+                move |trigger: Trigger<OnCollisionEnd>, mut zones: Query<&mut EmpZone>| {
+                    if let Ok(mut zone) = zones.get_mut(attach) {
+                        zone.leave(trigger.collider);
+                    }
+                },
+            );
+        }
+        PieceType::Teleportal => {
+            // anybody's ships go through, so no friendliness check here
+            sensor.observe(
+                move |trigger: Trigger<OnCollisionStart>,
+                      ships: Query<(), With<Spaceshipoid>>,
+                      mut portals: Query<&mut Teleportal>| {
+                    if ships.contains(trigger.collider) {
+                        if let Ok(mut portal) = portals.get_mut(attach) {
+                            portal.entered.push(trigger.collider);
+                        }
+                    }
+                },
+            );
+        }
         PieceType::BasicTurret
         | PieceType::BlastTurret
         | PieceType::LaserTurret
@@ -131,10 +200,25 @@ pub fn setup_sensor_observers(
 
 pub fn make_thing(
     mut commands: Commands,
-    broadcast: ResMut<Sender>,
     mut things: EventReader<PlaceEvent>,
     territories: Query<(&GamePiece, &Transform, Option<&Fabber>, Option<&Territory>)>,
+    clients: Res<ClientMap>,
+    channels: Query<&ClientChannel>,
+    mut refund: EventWriter<ClientCollectEvent>,
 ) {
+    // a client paid for this and it isn't happening: say why and give the money back
+    let mut reject = |ev: &PlaceEvent, reason: RejectionReason| {
+        let (Some(request_id), Some(client)) = (ev.request_id, clients.get(&ev.owner)) else {
+            return;
+        };
+        if let Ok(channel) = channels.get(*client) {
+            channel.reject(request_id, reason);
+        }
+        refund.write(ClientCollectEvent {
+            client: *client,
+            amount: ev.tp.price() as i32,
+        });
+    };
     'evloop: for ev in things.read() {
         let mut transform = Transform::from_xyz(ev.x, ev.y, 0.0);
         transform.rotate_z(ev.a);
@@ -172,7 +256,7 @@ pub fn make_thing(
                             if territory_holder.owner != ev.owner
                                 && (territory_holder.slot == 1 || territory_holder.slot != ev.slot)
                             {
-                                println!("too close!");
+                                reject(ev, RejectionReason::TooCloseToTerritory);
                                 piece.despawn();
                                 continue 'evloop;
                             }
@@ -182,6 +266,7 @@ pub fn make_thing(
             }
         }
         if !isfab {
+            reject(ev, RejectionReason::OutsideFabber);
             piece.despawn();
             continue;
         }
@@ -198,14 +283,6 @@ pub fn make_thing(
             PieceType::Castle => {
                 let terr = Territory::castle();
                 let fab = Fabber::castle();
-                let _ = broadcast.send(ServerMessage::Territory {
-                    id: piece.id().into(),
-                    radius: terr.radius,
-                });
-                let _ = broadcast.send(ServerMessage::Fabber {
-                    id: piece.id().into(),
-                    radius: fab.radius,
-                });
                 piece.insert((terr, fab));
                 health = 6.0;
             }
@@ -291,6 +368,13 @@ pub fn make_thing(
                 ));
                 health = 1.0;
             }
+            PieceType::Madecs => {
+                piece.insert((
+                    Spaceshipoid::of(Missile::hypersonic(), ev.x, ev.y),
+                    ClusterWarhead::madecs(),
+                ));
+                health = 1.0;
+            }
             PieceType::LaserNode => {
                 piece.insert(LaserNode::new(2));
             }
@@ -303,23 +387,72 @@ pub fn make_thing(
                     ScrapShip::new(),
                 ));
             }
+            PieceType::FleetDefenseShip => {
+                piece.insert((
+                    Spaceshipoid::of(Ship::normal(), ev.x, ev.y),
+                    PointDefense::new(40.0, 0.5, 6),
+                ));
+                health = 3.0;
+            }
             PieceType::BasicTurret => {
                 piece.insert((Turret::new(StandardTargeting), Gun::mediocre()));
             }
             PieceType::SmartTurret => {
-                piece.insert((Turret::new(SmartTargeting), Gun::mediocre()));
+                piece.insert((
+                    Turret::new(SmartTargeting::new(Gun::mediocre().bullets)),
+                    Gun::mediocre(),
+                ));
+            }
+            PieceType::BlastTurret => {
+                piece.insert((
+                    Turret::new(SmartTargeting::new(Gun::blast().bullets)),
+                    Gun::blast(),
+                ));
+                health = 4.0;
+            }
+            PieceType::OutpostConstructor
+            | PieceType::FortConstructor
+            | PieceType::CastleConstructor => {
+                piece.insert((
+                    Spaceshipoid::of(Ship::slow(), ev.x, ev.y),
+                    Constructor::new(ev.tp.constructs().unwrap()),
+                ));
+                health = 4.0;
+            }
+            PieceType::TeleportalConstructor => {
+                piece.insert((
+                    Spaceshipoid::of(Ship::slow(), ev.x, ev.y),
+                    Constructor::teleportal(),
+                ));
+                health = 4.0;
+            }
+            PieceType::Teleportal => {
+                // anchored, so it stays put when the constructor that just built it pulls away
+                piece.insert((Teleportal::new(ev.builder), RigidBody::Static));
+                health = 3.0;
+            }
+            PieceType::Outpost => {
+                piece.insert(Fabber::outpost());
+                health = 3.0;
+            }
+            PieceType::Fort => {
+                piece.insert((Territory::fort(), Fabber::fort()));
+                health = 5.0;
+            }
+            PieceType::EmpZone => {
+                piece.insert(EmpZone::new(0.3));
+                health = 5.0;
+            }
+            PieceType::LaserTurret => {
+                piece.insert((
+                    Turret::new(StandardTargeting),
+                    LaserEmitter::new(400.0, 1.5, 45),
+                ));
+                health = 4.0;
             }
             _ => {}
         };
         piece.insert(GamePiece::new(ev.tp, ev.owner, ev.slot, health));
-        let _ = broadcast.send(ServerMessage::ObjectCreate {
-            x: ev.x,
-            y: ev.y,
-            a: ev.a,
-            owner: ev.owner,
-            id: piece.id().into(),
-            tp: ev.tp,
-        });
         let id = piece.id();
         if let Some(radius) = ev.tp.sensor() {
             let sensor = commands.spawn((
@@ -17,6 +17,12 @@ pub use make_thing::*;
 pub mod client_tick;
 pub use client_tick::*;
 
+pub mod client_dropped;
+pub use client_dropped::*;
+
+pub mod client_resume;
+pub use client_resume::*;
+
 pub mod spaceshipoids;
 pub use spaceshipoids::*;
 
@@ -32,6 +38,15 @@ pub use lasers::*;
 pub mod scrapships;
 pub use scrapships::*;
 
+pub mod point_defense;
+pub use point_defense::*;
+
+pub mod laser_turrets;
+pub use laser_turrets::*;
+
+pub mod constructors;
+pub use constructors::*;
+
 pub mod turret;
 pub use turret::*;
 
@@ -53,6 +68,12 @@ pub use boom::*;
 pub mod explosion_clear;
 pub use explosion_clear::*;
 
+pub mod index_pieces;
+pub use index_pieces::*;
+
+pub mod update_vision;
+pub use update_vision::*;
+
 pub mod send_objects;
 pub use send_objects::*;
 
@@ -101,6 +122,18 @@ pub use strategy_path_handler::*;
 pub mod special_handler;
 pub use special_handler::*;
 
+pub mod fabber_upgrades;
+pub use fabber_upgrades::*;
+
+pub mod repair;
+pub use repair::*;
+
+pub mod teleportals;
+pub use teleportals::*;
+
+pub mod cluster_warheads;
+pub use cluster_warheads::*;
+
 pub mod client_flow_team;
 pub use client_flow_team::*;
 
@@ -109,3 +142,6 @@ pub use client_win_checks::*;
 
 pub mod client_money;
 pub use client_money::*;
+
+pub mod chat;
+pub use chat::*;
@@ -15,17 +15,18 @@
 use crate::components::*;
 use crate::events::*;
 use crate::resources::*;
+use crate::systems::cluster_warheads::split_cluster;
 use crate::Bullets;
 use crate::PieceType;
 use bevy::prelude::*;
-use common::comms::ServerMessage;
+use std::collections::HashSet;
 
 pub fn on_piece_dead(
     mut commands: Commands,
-    broadcast: ResMut<Sender>,
-    pieces: Query<&GamePiece>,
+    pieces: Query<(Entity, &GamePiece, &Transform)>,
     sensored: Query<&Sensored>,
-    bullets: Query<(&Bullet, &Transform)>,
+    bullets: Query<(&Bullet, &Transform, Option<&ClusterBomb>)>,
+    warheads: Query<&ClusterWarhead>,
     chests: Query<&Chest>,
     mut events: EventReader<PieceDestroyedEvent>,
     mut explosions: EventWriter<ExplosionEvent>,
@@ -33,17 +34,37 @@ pub fn on_piece_dead(
     clients: Res<ClientMap>,
     mut client_collect: EventWriter<ClientCollectEvent>,
 ) {
+    let mut dead = HashSet::new(); // a piece can get killed more than once in a frame (say, a glide bomb that's shot as it hits something)
     for evt in events.read() {
-        if let Ok(piece) = pieces.get(evt.piece) {
-            if let Ok((bullet, pos)) = bullets.get(evt.piece) {
-                if let Bullets::Bomb(explosion, _) = bullet.tp {
+        if !dead.insert(evt.piece) {
+            continue;
+        }
+        if let Ok((_, piece, pos)) = pieces.get(evt.piece) {
+            if let Ok((bullet, pos, cluster)) = bullets.get(evt.piece) {
+                if let Bullets::Bomb(explosion, _) | Bullets::GlideBomb(explosion, _) = bullet.tp {
                     explosions.write(ExplosionEvent {
                         x: pos.translation.x,
                         y: pos.translation.y,
                         props: explosion,
+                        source: evt.piece,
+                        owner: Some((piece.owner, piece.slot)),
+                        spares: cluster.map(|cluster| cluster.0),
                     });
                 }
             }
+            if let Ok(warhead) = warheads.get(evt.piece) {
+                split_cluster(
+                    &mut commands,
+                    evt.piece,
+                    warhead,
+                    piece,
+                    pos.translation.truncate(),
+                    pieces
+                        .iter()
+                        .filter(|(other, ..)| !dead.contains(other))
+                        .map(|(_, p, t)| (p, t)),
+                );
+            }
             if let Ok(_) = chests.get(evt.piece) {
                 if let Some(cl) = clients.get(&evt.responsible) {
                     client_collect.write(ClientCollectEvent {
@@ -52,7 +73,12 @@ pub fn on_piece_dead(
                     }); // kill the chest, collect some dough, that's life, yo!
                 } // [2025-8-20] sometimes I go back and read old comments and then I feel sad
             }
-            if piece.tp == PieceType::Castle {
+            // players can build more castles; they're only out once the last one goes
+            if piece.tp == PieceType::Castle
+                && !pieces.iter().any(|(other, p, _)| {
+                    p.tp == PieceType::Castle && p.owner == piece.owner && !dead.contains(&other)
+                })
+            {
                 client_kill.write(ClientKilledEvent {
                     client: piece.owner,
                 });
@@ -61,13 +87,6 @@ pub fn on_piece_dead(
             if let Ok(s) = sensored.get(evt.piece) {
                 commands.entity(s.sensor).despawn(); // despawn attached sensors
             }
-            if let Err(_) = broadcast.send(ServerMessage::DeleteObject {
-                id: evt.piece.into(),
-            }) {
-                println!(
-                    "game engine lost connection to webserver. this is probably not critical."
-                );
-            }
         }
     }
 }
@@ -0,0 +1,70 @@
+/*
+    Copyright 2024 Tyler Clarke.
+
+    This file is part of Exosphere.
+
+    Exosphere is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+    Exosphere is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License along with Exosphere. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// fleet defense ships zapping incoming bullets and missiles
+use crate::components::*;
+use crate::events::*;
+use bevy::prelude::*;
+
+pub fn point_defense(
+    mut ships: Query<(Entity, &mut PointDefense, &GamePiece, &Transform)>,
+    pieces: Query<(&GamePiece, &Transform)>,
+    mut lasers: EventWriter<LaserCastEvent>,
+) {
+    for (shipentity, mut ship, ship_piece, shippos) in ships.iter_mut() {
+        if ship.tick > 0 {
+            ship.tick -= 1;
+            continue;
+        }
+        let from = shippos.translation.truncate();
+        // missiles first (they hurt more), then whichever is closest
+        let mut target: Option<(bool, f32, Entity, Vec2)> = None;
+        let mut i = 0;
+        while i < ship.in_range.len() {
+            let e = ship.in_range[i];
+            let Ok((piece, pos)) = pieces.get(e) else {
+                ship.in_range.swap_remove(i);
+                continue;
+            };
+            i += 1;
+            if ship_piece.allied_with(piece) {
+                continue;
+            }
+            let missile = piece.tp.is_missile();
+            let off = pos.translation.truncate() - from;
+            let dist = off.length();
+            if dist > ship.range {
+                continue;
+            }
+            let better = match target {
+                Some((t_missile, t_dist, _, _)) => {
+                    (missile && !t_missile) || (missile == t_missile && dist < t_dist)
+                }
+                None => true,
+            };
+            if better {
+                target = Some((missile, dist, e, off));
+            }
+        }
+        if let Some((_, _, e, off)) = target {
+            lasers.write(LaserCastEvent {
+                caster: shipentity,
+                from,
+                dir: off.normalize_or_zero(),
+                max_dist: ship.range,
+                dmg: ship.dmg,
+                exclusive: Some(e),
+            });
+            ship.tick = ship.cd;
+        }
+    }
+}
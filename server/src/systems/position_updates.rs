@@ -10,36 +10,132 @@
     You should have received a copy of the GNU General Public License along with Exosphere. If not, see <https://www.gnu.org/licenses/>.
 */
 
-// sends position updates to clients
+// sends position updates to clients: one snapshot per client per tick, carrying only the pieces (of the ones it knows
+// about) that were resampled since the last snapshot that client acknowledged
 
 use crate::components::*;
 use crate::resources::*;
 use crate::solve_spaceship::loopify;
+use avian2d::prelude::*;
 use bevy::prelude::*;
-use common::comms::ServerMessage;
+use common::comms::{ServerMessage, SnapshotMove};
+use common::{PieceId, UPDATE_RATE};
+use std::f32::consts::{PI, TAU};
+
+const POSITION_TOLERANCE: f32 = 4.0; // how far off a piece's extrapolated position can get before it's resampled
+const ANGLE_TOLERANCE: f32 = 0.02; // radians
+const MAX_SAMPLE_AGE: u32 = 60; // ticks. moving pieces are resampled at least this often regardless
+
+pub type SampledPiece = (
+    Entity,
+    &'static Transform,
+    Option<&'static LinearVelocity>,
+    Option<&'static AngularVelocity>,
+);
 
 pub fn position_updates(
-    broadcast: ResMut<Sender>,
-    mut objects: Query<(Entity, &mut GamePiece, &Transform), Changed<Transform>>,
+    config: Res<Config>,
+    mut history: ResMut<SnapshotHistory>,
+    mut moved: Query<(&mut GamePiece, &Transform), Changed<Transform>>,
+    pieces: Query<SampledPiece, With<GamePiece>>,
+    clients: Query<(&ClientChannel, &ClientSnapshotAck, &ClientInterest), With<ClientConnected>>,
 ) {
-    for (entity, mut piece, transform) in objects.iter_mut() {
+    for (mut piece, transform) in moved.iter_mut() {
         let pos = transform.translation.truncate();
         let ang = transform.rotation.to_euler(EulerRot::ZYX).0;
-        // updates on position
+        // turrets lead their shots with this
         piece.c_vel = piece.last_update_pos - pos;
         if (pos - piece.last_update_pos).length() > 1.0
             || loopify(ang, piece.last_update_ang).abs() > 0.01
         {
-            // are basically straight lines.
-            let _ = broadcast.send(ServerMessage::ObjectMove {
-                // ignore the errors
-                id: entity.into(),
-                x: pos.x,
-                y: pos.y,
-                a: transform.rotation.to_euler(EulerRot::ZYX).0,
-            });
             piece.last_update_pos = pos;
             piece.last_update_ang = ang;
         }
     }
+    // dead reckoning: a piece keeps its last sample for as long as extrapolating that sample puts it close enough to
+    // where it really is. pieces flying straight (bullets, mostly) and pieces sitting still hardly ever need resampling
+    let sequence = history.latest() + 1;
+    let previous = history.get(history.latest());
+    let frame: SnapshotFrame = pieces
+        .iter()
+        .map(|(entity, transform, velocity, spin)| {
+            let id: PieceId = entity.into();
+            let pos = transform.translation;
+            let ang = transform.rotation.to_euler(EulerRot::ZYX).0;
+            if let Some((sample, at)) = previous.and_then(|frame| frame.get(&id)) {
+                let age = sequence - at;
+                let seconds = age as f32 / UPDATE_RATE as f32;
+                let (x, y, a) =
+                    sample.extrapolate(seconds, config.board.width, config.board.height);
+                let drift = Vec2::new(x - pos.x, y - pos.y).length();
+                let twist = ((a - ang + PI).rem_euclid(TAU) - PI).abs();
+                let stale = sample.is_moving() && age >= MAX_SAMPLE_AGE; // keeps ages small enough for a u8
+                if drift <= POSITION_TOLERANCE && twist <= ANGLE_TOLERANCE && !stale {
+                    return (id, (*sample, *at));
+                }
+            }
+            let sample = SnapshotMove::new(
+                id,
+                pos.x,
+                pos.y,
+                ang,
+                config.board.width,
+                config.board.height,
+            )
+            .with_velocity(
+                velocity.map_or(0.0, |v| v.x),
+                velocity.map_or(0.0, |v| v.y),
+                spin.map_or(0.0, |s| s.0),
+            );
+            (id, (sample, sequence))
+        })
+        .collect();
+    history.push(frame);
+    let current = history.get(sequence).unwrap();
+    for (channel, ack, interest) in clients.iter() {
+        // the client only hears about the pieces it knows about. a piece it learned about after its baseline is
+        // missing from it, so it's sent whether it moved or not
+        let known = interest
+            .known
+            .iter()
+            .filter_map(|(id, entered)| current.get(id).map(|sample| (sample, *entered)));
+        // samples go out with their age, so the client knows how far to extrapolate them
+        let aged = |(mv, at): &(SnapshotMove, u32)| SnapshotMove {
+            age: (sequence - at).min(u8::MAX as u32) as u8,
+            ..*mv
+        };
+        let message = match history.get(ack.acked) {
+            Some(baseline) => {
+                let moves: Vec<SnapshotMove> = known
+                    .filter(|(sample, entered)| {
+                        ack.acked <= *entered || baseline.get(&sample.0.id) != Some(sample)
+                    })
+                    .map(|(sample, _)| aged(sample))
+                    .collect();
+                let removed: Vec<PieceId> = interest
+                    .forgotten
+                    .iter()
+                    .filter(|(_, left)| ack.acked <= **left)
+                    .map(|(id, _)| *id)
+                    .collect();
+                if moves.is_empty() && removed.is_empty() {
+                    continue; // nothing's changed since the client's baseline
+                }
+                ServerMessage::Snapshot {
+                    sequence,
+                    baseline: ack.acked,
+                    moves,
+                    removed,
+                }
+            }
+            // no baseline (new client, or it's fallen too far behind): send everything
+            None => ServerMessage::Snapshot {
+                sequence,
+                baseline: 0,
+                moves: known.map(|(sample, _)| aged(sample)).collect(),
+                removed: vec![],
+            },
+        };
+        channel.send(message);
+    }
 }
@@ -0,0 +1,143 @@
+/*
+    Copyright 2024 Tyler Clarke.
+
+    This file is part of Exosphere.
+
+    Exosphere is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+    Exosphere is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License along with Exosphere. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// fixing up damaged pieces. anything sitting in a friendly fabber that could build it gets patched up a little every second
+// while we're moving ships, and owners can pay to have a piece fixed up completely at any point in the game.
+// either way it costs money, in proportion to how much health is being put back
+use crate::components::*;
+use crate::consts::UPDATE_RATE;
+use crate::events::*;
+use crate::resources::*;
+use bevy::prelude::*;
+use common::comms::*;
+use common::fab::FabLevels;
+use common::types::PieceType;
+use common::PlayerId;
+
+const REPAIR_RATE: f32 = 1.0; // health per second restored in a fabber during move-ships
+
+fn full_repair_price(tp: PieceType) -> f32 {
+    // fixing something from nothing costs half what it did new.
+    // the big buildings are free to place, so they're priced at the constructor that makes them
+    let price = match tp {
+        PieceType::Castle => PieceType::CastleConstructor.price(),
+        PieceType::Fort => PieceType::FortConstructor.price(),
+        PieceType::Outpost => PieceType::OutpostConstructor.price(),
+        _ => tp.price(),
+    };
+    price as f32 / 2.0
+}
+
+struct FabberSpot {
+    owner: PlayerId,
+    slot: u8,
+    pos: Vec2,
+    radius: f32,
+    levels: FabLevels,
+}
+
+impl FabberSpot {
+    fn can_repair(&self, piece: &GamePiece, pos: Vec2) -> bool {
+        // same rules as placing: ours or our team's, in range, and able to build the thing in the first place
+        allies((self.owner, self.slot), (piece.owner, piece.slot))
+            && self.pos.distance_squared(pos) < self.radius * self.radius
+            && self.levels >= piece.tp.fabber()
+    }
+}
+
+pub fn repair(
+    mut requests: EventReader<RepairEvent>,
+    state: Res<GameState>,
+    mut spending: ResMut<Spending>,
+    mut pieces: Query<(Entity, &mut GamePiece, &Transform, Option<&Fabber>)>,
+    clients: Res<ClientMap>,
+    channels: Query<(&ClientChannel, &ClientMoney)>,
+    mut client_collect: EventWriter<ClientCollectEvent>,
+) {
+    let automatic =
+        state.playing && state.strategy && state.tick.is_multiple_of(UPDATE_RATE as u16);
+    let mut requests = requests.read().peekable();
+    if !automatic && requests.peek().is_none() {
+        return;
+    }
+    let fabbers: Vec<FabberSpot> = pieces
+        .iter()
+        .filter_map(|(_, piece, transform, fabber)| {
+            fabber.map(|fabber| FabberSpot {
+                owner: piece.owner,
+                slot: piece.slot,
+                pos: transform.translation.truncate(),
+                radius: fabber.radius,
+                levels: fabber.levels,
+            })
+        })
+        .collect();
+    // heal a piece by up to `amount`, if the owner can cover it. returns the price, or None if they're broke
+    let mut fix = |piece: &mut GamePiece, entity: Entity, amount: f32| -> Option<u32> {
+        let client = *clients.get(&piece.owner)?;
+        let (channel, money) = channels.get(client).ok()?;
+        let amount = amount.min(piece.start_health - piece.health);
+        let price = (full_repair_price(piece.tp) * amount / piece.start_health).ceil() as u32;
+        if !spending.spend(client, money, price) {
+            return None;
+        }
+        if price > 0 {
+            client_collect.write(ClientCollectEvent {
+                client,
+                amount: -(price as i32),
+            });
+        }
+        piece.health += amount;
+        channel.send(ServerMessage::Health {
+            id: entity.into(),
+            health: piece.health / piece.start_health,
+        });
+        Some(price)
+    };
+    for RepairEvent { piece, request_id } in requests {
+        let Ok((entity, mut piece, transform, _)) = pieces.get_mut(*piece) else {
+            continue; // died in the meantime
+        };
+        let Some(channel) = clients
+            .get(&piece.owner)
+            .and_then(|c| channels.get(*c).ok())
+            .map(|(c, _)| c)
+        else {
+            continue;
+        };
+        if piece.health >= piece.start_health {
+            channel.reject(*request_id, RejectionReason::NotDamaged);
+            continue;
+        }
+        let pos = transform.translation.truncate();
+        if !fabbers.iter().any(|fabber| fabber.can_repair(&piece, pos)) {
+            channel.reject(*request_id, RejectionReason::OutsideFabber);
+            continue;
+        }
+        let missing = piece.start_health - piece.health;
+        if fix(&mut piece, entity, missing).is_none() {
+            channel.reject(*request_id, RejectionReason::InsufficientFunds);
+        }
+    }
+    if automatic {
+        for (entity, mut piece, transform, _) in pieces.iter_mut() {
+            if piece.health <= 0.0 || piece.health >= piece.start_health {
+                continue;
+            }
+            let pos = transform.translation.truncate();
+            if fabbers.iter().any(|fabber| fabber.can_repair(&piece, pos)) {
+                // if they can't pay, it just stays broken
+                let _ = fix(&mut piece, entity, REPAIR_RATE);
+            }
+        }
+    }
+}
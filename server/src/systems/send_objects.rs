@@ -10,52 +10,135 @@
     You should have received a copy of the GNU General Public License along with Exosphere. If not, see <https://www.gnu.org/licenses/>.
 */
 
-// sends objects to new clients
+// keeps each client's copy of the board in sync: pieces that come into its area of interest are sent to it, and pieces
+// that leave it (or die, or go out of sight under fog of war) are deleted. clients always know about their own pieces,
+// wherever they are
 
 use crate::components::*;
-use crate::events::*;
 use crate::resources::*;
+use crate::systems::IndexedPiece;
 use bevy::prelude::*;
 use common::comms::ServerMessage;
+use common::{PieceId, PlayerId};
+use std::collections::HashSet;
+
+type ClientView = (
+    &'static ClientMeta,
+    Option<&'static ClientAffiliation>,
+    &'static ClientChannel,
+    Option<&'static ClientViewport>,
+    &'static mut ClientInterest,
+);
 
 pub fn send_objects(
-    mut events: EventReader<NewClientEvent>,
-    mut clients: ResMut<ClientMap>,
-    objects: Query<(
-        Entity,
-        &GamePiece,
-        &Transform,
-        Option<&Territory>,
-        Option<&Fabber>,
-    )>,
-    channel: Query<&ClientChannel>,
+    config: Res<Config>,
+    index: Res<PieceIndex>,
+    vision: Res<Vision>,
+    history: Res<SnapshotHistory>,
+    mut clients: Query<ClientView, With<ClientConnected>>,
+    objects: Query<IndexedPiece>,
 ) {
-    for ev in events.read() {
-        if let Some(client) = clients.get_mut(&ev.id) {
-            for (entity, piece, transform, territory, fabber) in objects.iter() {
-                let chan = channel.get(*client).unwrap();
-
-                chan.send(ServerMessage::ObjectCreate {
-                    x: transform.translation.x,
-                    y: transform.translation.y,
-                    a: transform.rotation.to_euler(EulerRot::ZYX).0,
-                    owner: piece.owner,
-                    id: entity.into(),
-                    tp: piece.tp,
+    let latest = history.latest();
+    let board = Rect::new(0.0, 0.0, config.board.width, config.board.height);
+    let margin = config.network.interest_margin;
+    for (meta, affiliation, chan, viewport, mut interest) in clients.iter_mut() {
+        // pieces get sent once they're within the margin, and deleted once they're twice that far out, so things
+        // hovering around the edge don't flicker in and out
+        let (enter, leave) = match viewport {
+            Some(ClientViewport(view)) if config.network.area_of_interest => (
+                Some(view.inflate(margin).intersect(board.inflate(margin))),
+                Some(
+                    view.inflate(margin * 2.0)
+                        .intersect(board.inflate(margin * 2.0)),
+                ),
+            ),
+            _ => (None, None),
+        };
+        interest.region = enter;
+        let interest = &mut *interest;
+        // with fog of war on, pieces that aren't ours (or our team's) also have to be in sight. spectators see it all
+        let slot = affiliation.map_or(0, |a| a.slot);
+        let in_sight = |entity: &Entity| {
+            let owner = objects
+                .get(*entity)
+                .ok()
+                .map(|(_, piece, ..)| (piece.owner, piece.slot));
+            vision.sees(meta.id, slot, *entity, owner)
+        };
+        let keep: HashSet<PieceId> = match leave {
+            Some(leave) => index
+                .within(leave)
+                .chain(index.owned_by(meta.id).iter().copied())
+                .filter(in_sight)
+                .map(Into::into)
+                .collect(),
+            None => objects
+                .iter()
+                .map(|(entity, ..)| entity)
+                .filter(in_sight)
+                .map(Into::into)
+                .collect(),
+        };
+        interest.known.retain(|id, _| {
+            if keep.contains(id) {
+                true
+            } else {
+                chan.send(ServerMessage::DeleteObject { id: *id });
+                interest.forgotten.insert(*id, latest);
+                false
+            }
+        });
+        // snapshots older than the history are never used as baselines, so anything forgotten before then is moot
+        interest
+            .forgotten
+            .retain(|_, seq| *seq + SnapshotHistory::DEPTH as u32 > latest);
+        let entering: Vec<Entity> = match enter {
+            Some(enter) => index
+                .within(enter)
+                .chain(index.owned_by(meta.id).iter().copied())
+                .filter(in_sight)
+                .collect(),
+            None => objects
+                .iter()
+                .map(|(entity, ..)| entity)
+                .filter(in_sight)
+                .collect(),
+        };
+        for entity in entering {
+            let id: PieceId = entity.into();
+            if interest.known.contains_key(&id) {
+                continue;
+            }
+            let Ok((_, piece, transform, territory, fabber, bullet)) = objects.get(entity) else {
+                continue;
+            };
+            chan.send(ServerMessage::ObjectCreate {
+                x: transform.translation.x,
+                y: transform.translation.y,
+                a: transform.rotation.to_euler(EulerRot::ZYX).0,
+                owner: if bullet {
+                    PlayerId::SYSTEM
+                } else {
+                    piece.owner
+                },
+                id,
+                tp: piece.tp,
+            });
+            if let Some(territory) = territory {
+                chan.send(ServerMessage::Territory {
+                    id,
+                    radius: territory.radius,
+                });
+            }
+            if let Some(fabber) = fabber {
+                chan.send(ServerMessage::Fabber {
+                    id,
+                    radius: fabber.radius,
+                    levels: fabber.levels,
                 });
-                if let Some(territory) = territory {
-                    chan.send(ServerMessage::Territory {
-                        id: entity.into(),
-                        radius: territory.radius,
-                    });
-                }
-                if let Some(fabber) = fabber {
-                    chan.send(ServerMessage::Fabber {
-                        id: entity.into(),
-                        radius: fabber.radius,
-                    });
-                }
             }
+            interest.known.insert(id, latest);
+            interest.forgotten.remove(&id);
         }
     }
 }
@@ -14,17 +14,17 @@ use crate::components::*;
 use crate::events::*;
 use crate::resources::*;
 use bevy::prelude::*;
-use common::comms::ServerMessage;
+use common::comms::{Features, ServerMessage};
 
 pub fn setup_client(
     mut events: EventReader<ClientSuccessfullyJoinedEvent>,
-    mut ev_newclient: EventWriter<NewClientEvent>,
     config: Res<Config>,
     clients: Res<ClientMap>,
     broadcast: ResMut<Sender>,
     channels: Query<&ClientChannel>,
     affiliations: Query<&ClientAffiliation>,
     meta: Query<&ClientMeta>,
+    features: Query<&ClientFeatures>,
     mut commands: Commands,
 ) {
     for ClientSuccessfullyJoinedEvent(client) in events.read() {
@@ -50,6 +50,16 @@ pub fn setup_client(
             board_width: config.board.width,
             board_height: config.board.height,
             slot,
+            // a client that can't resume doesn't get a token to resume with
+            resume_token: if features
+                .get(client)
+                .is_ok_and(|f| f.0.contains(Features::RESUME))
+            {
+                meta.get(client).unwrap().resume_token
+            } else {
+                0
+            },
+            fog_of_war: config.fog_of_war,
         });
         if let Err(_) = broadcast.send(ServerMessage::PlayerData {
             id,
@@ -58,7 +68,10 @@ pub fn setup_client(
         }) {
             println!("couldn't broadcast player data");
         }
-        commands.entity(client).insert(ClientConnected);
-        ev_newclient.write(NewClientEvent { id });
+        commands.entity(client).insert((
+            ClientConnected,
+            ClientSnapshotAck { acked: 0 },
+            ClientInterest::default(),
+        )); // whatever it had before, it's starting from scratch. send_objects fills it in
     }
 }
@@ -13,18 +13,35 @@
 // fires bullets from items with guns
 
 use crate::components::*;
-use crate::resources::*;
 use crate::PieceType;
 use avian2d::prelude::*;
 use bevy::prelude::*;
-use common::comms::ServerMessage;
 use common::PlayerId;
 use rand::Rng;
 
 #[derive(Copy, Clone)]
 pub enum Bullets {
-    MinorBullet(u16),               // simple bullet with range
+    MinorBullet(u16),                    // simple bullet with range
     Bomb(ExplosionProperties, u16), // properties of the explosion we're boutta detonate, range of the bullet
+    GlideBomb(ExplosionProperties, u16), // slow, long-lived bomb that also goes off on contact. same fields as Bomb
+}
+
+impl Bullets {
+    pub fn speed(&self) -> f32 {
+        // muzzle velocity, on top of whatever the gun itself is doing
+        match self {
+            Bullets::GlideBomb(_, _) => 250.0,
+            _ => 450.0,
+        }
+    }
+
+    pub fn range(&self) -> u16 {
+        match self {
+            Bullets::MinorBullet(range)
+            | Bullets::Bomb(_, range)
+            | Bullets::GlideBomb(_, range) => *range,
+        }
+    }
 }
 
 fn discharge_barrel(
@@ -34,66 +51,55 @@ fn discharge_barrel(
     gun: &Gun,
     position: &Transform,
     velocity: &LinearVelocity,
-    broadcast: &ResMut<Sender>,
 ) {
     let ang = position.rotation.to_euler(EulerRot::ZYX).0;
-    let vel = LinearVelocity(**velocity + glam::f32::Vec2::from_angle(ang) * 450.0);
+    let vel = LinearVelocity(**velocity + glam::f32::Vec2::from_angle(ang) * gun.bullets.speed());
     let mut transform = position.clone();
     transform.translation += (Vec2::from_angle(ang) * gun.center_offset).extend(0.0);
     transform.translation += (Vec2::from_angle(ang).perp()
         * gun.barrel_spacing
         * (barrel as f32 - gun.barrels as f32 / 2.0 + 0.5))
         .extend(0.0);
-    match gun.bullets {
-        Bullets::MinorBullet(range) => {
-            let piece = commands.spawn((
-                GamePiece::new(PieceType::Bullet, owner, 0, 0.5),
-                RigidBody::Dynamic,
-                PieceType::shape(&PieceType::Bullet).to_collider(),
-                vel,
-                transform,
-                TimeToLive { lifetime: range },
-                Bullet { tp: gun.bullets },
-                CollisionEventsEnabled,
-                PresolveVelocity(Vec2::new(0.0, 0.0)),
-            ));
-            let _ = broadcast.send(ServerMessage::ObjectCreate {
-                x: transform.translation.x,
-                y: transform.translation.y,
-                a: ang,
-                owner: PlayerId::SYSTEM,
-                id: piece.id().into(),
-                tp: PieceType::Bullet,
-            });
-        }
-        Bullets::Bomb(_, range) => {
-            let piece = commands.spawn((
-                GamePiece::new(PieceType::SmallBomb, owner, 0, 0.5),
-                RigidBody::Dynamic,
-                PieceType::shape(&PieceType::SmallBomb).to_collider(),
-                vel,
-                transform,
-                TimeToLive { lifetime: range },
-                Bullet { tp: gun.bullets },
-                CollisionEventsEnabled,
-                PresolveVelocity(Vec2::new(0.0, 0.0)),
-            ));
-            let _ = broadcast.send(ServerMessage::ObjectCreate {
-                x: transform.translation.x,
-                y: transform.translation.y,
-                a: ang,
-                owner: PlayerId::SYSTEM,
-                id: piece.id().into(),
-                tp: PieceType::SmallBomb,
-            });
-        }
-    }
+    spawn_bullet(
+        commands,
+        owner,
+        gun.bullets,
+        transform,
+        vel,
+        gun.bullets.range(),
+    );
+}
+
+pub(crate) fn spawn_bullet(
+    commands: &mut Commands,
+    owner: PlayerId,
+    bullets: Bullets,
+    transform: Transform,
+    vel: LinearVelocity,
+    lifetime: u16,
+) -> Entity {
+    // lifetime is usually just the range, but cluster warheads time their bombs to go off over the target
+    let tp = match bullets {
+        Bullets::MinorBullet(_) => PieceType::Bullet,
+        Bullets::Bomb(_, _) | Bullets::GlideBomb(_, _) => PieceType::SmallBomb,
+    };
+    let bullet = commands.spawn((
+        GamePiece::new(tp, owner, 0, 0.5),
+        RigidBody::Dynamic,
+        tp.shape().to_collider(),
+        vel,
+        transform,
+        TimeToLive { lifetime },
+        Bullet { tp: bullets },
+        CollisionEventsEnabled,
+        PresolveVelocity(Vec2::new(0.0, 0.0)),
+    ));
+    bullet.id()
 }
 
 pub fn shoot(
     mut commands: Commands,
     mut pieces: Query<(&Transform, &LinearVelocity, &GamePiece, &mut Gun)>,
-    broadcast: ResMut<Sender>,
 ) {
     for (position, velocity, piece, mut gun) in pieces.iter_mut() {
         if gun.enabled {
@@ -113,7 +119,6 @@ pub fn shoot(
                         &gun,
                         position,
                         velocity,
-                        &broadcast,
                     );
                 } else {
                     for barrel in 0..gun.barrels {
@@ -124,7 +129,6 @@ pub fn shoot(
                             &gun,
                             position,
                             velocity,
-                            &broadcast,
                         );
                     }
                 }
@@ -20,6 +20,7 @@ use avian2d::prelude::*;
 use bevy::prelude::*;
 use common::comms::ServerMessage;
 use common::pathfollower::PathNode;
+use std::collections::HashMap;
 
 pub fn move_spaceshipoids(
     mut shipoids: Query<(
@@ -36,7 +37,15 @@ pub fn move_spaceshipoids(
     targetables: Query<&Transform>,
     mut clients: ResMut<ClientMap>,
     chan: Query<&ClientChannel>,
+    emp_zones: Query<&EmpZone>,
 ) {
+    // how much thrust every ship stuck in an emp field gets to keep. overlapping fields stack
+    let mut emp: HashMap<Entity, f32> = HashMap::new();
+    for zone in emp_zones.iter() {
+        for e in zone.affected.iter() {
+            *emp.entry(*e).or_insert(1.0) *= zone.slow;
+        }
+    }
     for (
         mut impulse,
         mut torque,
@@ -52,6 +61,7 @@ pub fn move_spaceshipoids(
         let mut goal = spaceship.kinematics.node_override();
         let mut is_override = true;
         let mass = collider.mass_properties(1.0).mass;
+        let thrust = emp.get(&entity).copied().unwrap_or(1.0);
         if let None = goal {
             is_override = false;
             goal = spaceship.pathfollower.get_next();
@@ -81,11 +91,11 @@ pub fn move_spaceshipoids(
                 }
             } {
                 KinematicResult::Thrust(imp, t) => {
-                    impulse.set_impulse(imp * mass);
+                    impulse.set_impulse(imp * mass * thrust);
                     torque.set_torque(t * mass);
                 }
                 KinematicResult::Done(imp, t) => {
-                    impulse.set_impulse(imp * mass);
+                    impulse.set_impulse(imp * mass * thrust);
                     torque.set_torque(t * mass);
                     if is_override {
                         spaceship.kinematics.override_complete();
@@ -13,25 +13,89 @@
 use crate::components::*;
 use crate::events::*;
 use crate::resources::*;
+use avian2d::prelude::*;
 use bevy::prelude::*;
 use common::comms::*;
 
 pub fn special_handler(
+    mut commands: Commands,
     mut events: EventReader<ClientSpecialObjectEvent>,
     state: Res<GameState>,
-    mut guns_pieces: Query<(&GamePiece, &mut Gun)>,
-    client_meta: Query<&ClientMeta>,
+    mut pieces: Query<(
+        &GamePiece,
+        &Transform,
+        Option<&mut Gun>,
+        Option<&mut Constructor>,
+    )>,
+    territories: Query<(&GamePiece, &Transform, &Territory)>,
+    clients: Query<(&ClientMeta, &ClientChannel)>,
+    mut repair: EventWriter<RepairEvent>,
 ) {
-    if state.playing && state.strategy {
-        for ClientSpecialObjectEvent(client, id, evt) in events.read() {
-            match evt {
-                ObjectSpecialPropertySet::GunState(state) => {
-                    if let Ok((piece, mut gun)) = guns_pieces.get_mut((*id).into()) {
-                        if piece.owner == client_meta.get(*client).unwrap().id {
-                            gun.enabled = *state;
-                        }
+    'events: for ClientSpecialObjectEvent(client, request_id, id, evt) in events.read() {
+        let Ok((meta, channel)) = clients.get(*client) else {
+            continue;
+        };
+        // guns can only be flipped while we're moving ships, but constructors can start building (and pieces can be repaired) whenever
+        let allowed = match evt {
+            ObjectSpecialPropertySet::GunState(_) => state.playing && state.strategy,
+            ObjectSpecialPropertySet::Construct | ObjectSpecialPropertySet::Repair => state.playing,
+        };
+        if !allowed {
+            channel.reject(*request_id, RejectionReason::WrongStage);
+            continue;
+        }
+        let Some((entity, Ok((piece, transform, gun, constructor)))) =
+            id.entity().map(|e| (e, pieces.get_mut(e)))
+        else {
+            channel.reject(*request_id, RejectionReason::NoSuchPiece);
+            continue;
+        };
+        if piece.owner != meta.id {
+            channel.reject(*request_id, RejectionReason::NotYours);
+            continue;
+        }
+        match evt {
+            ObjectSpecialPropertySet::GunState(state) => {
+                if let Some(mut gun) = gun {
+                    gun.enabled = *state;
+                } else {
+                    channel.reject(*request_id, RejectionReason::NoGun);
+                }
+            }
+            ObjectSpecialPropertySet::Construct => {
+                let Some(mut constructor) = constructor else {
+                    channel.reject(*request_id, RejectionReason::NotAConstructor);
+                    continue;
+                };
+                if constructor.remaining.is_some() {
+                    continue; // already on it
+                }
+                // same rule as placing a castle: no overlapping anyone else's territory. outposts don't hold any,
+                // but they still can't go up inside someone else's
+                let reach = Territory::of(constructor.builds).map_or(0.0, |t| t.radius);
+                for (holder, position, other) in territories.iter() {
+                    if !holder.allied_with(piece)
+                        && position.translation.distance(transform.translation)
+                            < other.radius + reach
+                    {
+                        channel.reject(*request_id, RejectionReason::TooCloseToTerritory);
+                        continue 'events;
                     }
                 }
+                constructor.remaining = Some(constructor.build_time);
+                // pinned in place until it's done. no more paths, no more thrust
+                commands.entity(entity).remove::<Spaceshipoid>().insert((
+                    RigidBody::Static,
+                    LinearVelocity::ZERO,
+                    AngularVelocity::ZERO,
+                ));
+            }
+            ObjectSpecialPropertySet::Repair => {
+                // fabber and money checks live with the rest of the repair logic
+                repair.write(RepairEvent {
+                    piece: entity,
+                    request_id: *request_id,
+                });
             }
         }
     }
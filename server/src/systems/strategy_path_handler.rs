@@ -19,43 +19,41 @@ use common::comms::*;
 pub fn strategy_path_handler(
     mut events: EventReader<StrategyPathModifiedEvent>,
     state: Res<GameState>,
-    client_meta: Query<&ClientMeta>,
-    mut pieces: Query<(&GamePiece, &mut Spaceshipoid)>,
+    clients: Query<(&ClientMeta, &ClientChannel)>,
+    mut pieces: Query<(&GamePiece, Option<&mut Spaceshipoid>)>,
 ) {
-    if state.playing && state.strategy {
-        for StrategyPathModifiedEvent(client, event) in events.read() {
-            let id = client_meta.get(*client).unwrap().id;
-            match event {
-                StrategyPathModification::Insert(piece, index, node) => {
-                    if let Ok((piece, mut shipoid)) = pieces.get_mut((*piece).into()) {
-                        if piece.owner == id {
-                            shipoid.pathfollower.insert_node(*index, *node);
-                        } else {
-                            println!("client attempted to move thing it doesn't own [how rude]");
-                        }
-                    }
-                }
-                StrategyPathModification::Clear(piece) => {
-                    if let Ok((piece, mut shipoid)) = pieces.get_mut((*piece).into()) {
-                        if piece.owner == id {
-                            shipoid.pathfollower.clear();
-                        }
-                    }
-                }
-                StrategyPathModification::Set(piece, index, node) => {
-                    if let Ok((piece, mut shipoid)) = pieces.get_mut((*piece).into()) {
-                        if piece.owner == id {
-                            shipoid.pathfollower.update_node(*index, *node);
-                        }
-                    }
-                }
-                StrategyPathModification::Delete(piece, index) => {
-                    if let Ok((piece, mut shipoid)) = pieces.get_mut((*piece).into()) {
-                        if piece.owner == id {
-                            shipoid.pathfollower.remove_node(*index);
-                        }
-                    }
-                }
+    for StrategyPathModifiedEvent(client, request_id, event) in events.read() {
+        let Ok((meta, channel)) = clients.get(*client) else {
+            continue;
+        };
+        if !(state.playing && state.strategy) {
+            channel.reject(*request_id, RejectionReason::WrongStage);
+            continue;
+        }
+        let Some(Ok((piece, shipoid))) = event.piece().entity().map(|e| pieces.get_mut(e)) else {
+            channel.reject(*request_id, RejectionReason::NoSuchPiece);
+            continue;
+        };
+        if piece.owner != meta.id {
+            channel.reject(*request_id, RejectionReason::NotYours);
+            continue;
+        }
+        let Some(mut shipoid) = shipoid else {
+            channel.reject(*request_id, RejectionReason::NotMovable);
+            continue;
+        };
+        match event {
+            StrategyPathModification::Insert(_, index, node) => {
+                shipoid.pathfollower.insert_node(*index, *node);
+            }
+            StrategyPathModification::Clear(_) => {
+                shipoid.pathfollower.clear();
+            }
+            StrategyPathModification::Set(_, index, node) => {
+                shipoid.pathfollower.update_node(*index, *node);
+            }
+            StrategyPathModification::Delete(_, index) => {
+                shipoid.pathfollower.remove_node(*index);
             }
         }
     }
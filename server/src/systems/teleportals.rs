@@ -0,0 +1,94 @@
+/*
+    Copyright 2024 Tyler Clarke.
+
+    This file is part of Exosphere.
+
+    Exosphere is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+    Exosphere is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License along with Exosphere. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// teleportals: pairing up the two ends of each pair, shipping things between them, and coming apart when one end dies
+use crate::components::*;
+use crate::consts::UPDATE_RATE;
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use common::types::PieceType;
+use std::collections::HashSet;
+
+// where a ship is, which way it's going, and how big it is
+type Traveller = (
+    &'static mut Transform,
+    &'static LinearVelocity,
+    &'static GamePiece,
+);
+
+const COOLDOWN: u16 = UPDATE_RATE as u16 * 2; // so a ship that turns straight back doesn't bounce between the ends
+
+pub fn teleportals(
+    mut commands: Commands,
+    mut portals: Query<(Entity, &mut Teleportal, &Transform)>,
+    mut ships: Query<Traveller, (With<Spaceshipoid>, Without<Teleportal>)>,
+    mut cooldowns: Query<(Entity, &mut TeleportCooldown)>,
+) {
+    for (entity, mut cooldown) in cooldowns.iter_mut() {
+        if cooldown.0 == 0 {
+            commands.entity(entity).remove::<TeleportCooldown>();
+        } else {
+            cooldown.0 -= 1;
+        }
+    }
+    let ends: Vec<(Entity, Option<Entity>, Option<Entity>, Vec3)> = portals
+        .iter()
+        .map(|(entity, portal, transform)| {
+            (entity, portal.builder, portal.link, transform.translation)
+        })
+        .collect();
+    let mut moves = vec![];
+    for (entity, mut portal, _) in portals.iter_mut() {
+        if let Some(link) = portal.link {
+            if !ends.iter().any(|(other, ..)| *other == link) {
+                // the other end is gone, so this one doesn't go anywhere anymore
+                portal.link = None;
+            }
+        } else if let Some(builder) = portal.builder {
+            // both ends come from the same constructor. once they've found each other that's all it's good for
+            if let Some((other, ..)) = ends.iter().find(|(other, other_builder, other_link, _)| {
+                *other != entity && *other_builder == Some(builder) && other_link.is_none()
+            }) {
+                portal.link = Some(*other);
+                portal.builder = None;
+            }
+        }
+        let entered = std::mem::take(&mut portal.entered);
+        let Some(there) = portal
+            .link
+            .and_then(|link| ends.iter().find(|(other, ..)| *other == link))
+            .map(|(.., there)| *there)
+        else {
+            continue;
+        };
+        for ship in entered {
+            moves.push((ship, there));
+        }
+    }
+    let mut moved = HashSet::new();
+    let field = PieceType::Teleportal.sensor().unwrap_or(0.0);
+    for (ship, there) in moves {
+        if cooldowns.contains(ship) || !moved.insert(ship) {
+            continue;
+        }
+        if let Ok((mut ship_transform, velocity, piece)) = ships.get_mut(ship) {
+            // out the far side of the other end, the way it was going, and clear of that end's field.
+            // ships that were somehow sitting still come out the way they're facing
+            let facing = Vec2::from_angle(ship_transform.rotation.to_euler(EulerRot::ZYX).0);
+            let dir = velocity.normalize_or(facing);
+            let (w, h) = piece.tp.shape().to_bbox();
+            let out = field + Vec2::new(w, h).length() / 2.0;
+            ship_transform.translation = there + (dir * out).extend(0.0);
+            commands.entity(ship).insert(TeleportCooldown(COOLDOWN));
+        }
+    }
+}
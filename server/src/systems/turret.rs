@@ -29,9 +29,7 @@ pub fn turrets(
     for (mut turret, turret_piece, mut torque, turret_pos, turret_angvel) in turrets.iter_mut() {
         for i in 0..turret.in_range.len() {
             if let Ok((piece, position, _, _)) = pieces.get(turret.in_range[i]) {
-                if turret_piece.owner == piece.owner
-                    || (turret_piece.slot > 1 && turret_piece.slot == piece.slot)
-                {
+                if turret_piece.allied_with(piece) {
                     continue;
                 }
                 if turret.targeting_algorithm.will_attack(piece.tp) {
@@ -0,0 +1,56 @@
+/*
+    Copyright 2024 Tyler Clarke.
+
+    This file is part of Exosphere.
+
+    Exosphere is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+    Exosphere is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License along with Exosphere. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// works out what everyone can see through the fog of war: every piece within vision range of one of theirs
+
+use crate::components::*;
+use crate::resources::*;
+use bevy::prelude::*;
+
+pub fn update_vision(
+    config: Res<Config>,
+    index: Res<PieceIndex>,
+    mut vision: ResMut<Vision>,
+    players: Query<(&ClientMeta, &ClientAffiliation)>,
+    pieces: Query<(&GamePiece, &Transform)>,
+) {
+    vision.clear();
+    if !config.fog_of_war {
+        return;
+    }
+    for (meta, affiliation) in players.iter() {
+        if affiliation.slot == 0 {
+            continue; // spectators see everything
+        }
+        let seen = vision
+            .entry(Viewer::of(meta.id, affiliation.slot))
+            .or_default();
+        for eye in index.owned_by(meta.id) {
+            let Ok((piece, transform)) = pieces.get(*eye) else {
+                continue;
+            };
+            let range = piece.tp.vision();
+            if range <= 0.0 {
+                continue;
+            }
+            let eye = transform.translation.truncate();
+            let area = Rect::from_center_half_size(eye, Vec2::splat(range));
+            for other in index.within(area) {
+                if let Ok((_, transform)) = pieces.get(other) {
+                    if transform.translation.truncate().distance_squared(eye) <= range * range {
+                        seen.insert(other);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+// tls termination for the websocket server, built with the `tls` feature.
+// rustls does all the actual work; this just shuffles bytes between it and a non-blocking TcpStream
+// so the websocket server can treat a TlsStream (almost) exactly like a socket.
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ServerConfig, ServerConnection};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+
+// how much plaintext rustls may buffer on the way out. anything past this stays in the client's outbox,
+// where it can be coalesced and counted against the backpressure budget
+const SEND_BUFFER_LIMIT: usize = 64 * 1024;
+
+pub fn load_config(cert_file: &str, key_file: &str) -> Result<Arc<ServerConfig>, String> {
+    let certs = CertificateDer::pem_file_iter(cert_file)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("couldn't load certificates from {}: {}", cert_file, e))?;
+    if certs.is_empty() {
+        return Err(format!("no certificates in {}", cert_file));
+    }
+    let key = PrivateKeyDer::from_pem_file(key_file)
+        .map_err(|e| format!("couldn't load a private key from {}: {}", key_file, e))?;
+    let config =
+        ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| e.to_string())?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|e| format!("bad certificate or key: {}", e))?;
+    Ok(Arc::new(config))
+}
+
+pub struct TlsStream {
+    conn: ServerConnection,
+    socket: TcpStream,
+}
+
+impl TlsStream {
+    pub fn new(config: Arc<ServerConfig>, socket: TcpStream) -> io::Result<Self> {
+        let mut conn = ServerConnection::new(config).map_err(io::Error::other)?;
+        conn.set_buffer_limit(Some(SEND_BUFFER_LIMIT));
+        Ok(Self { conn, socket })
+    }
+
+    pub fn socket(&self) -> &TcpStream {
+        &self.socket
+    }
+
+    pub fn wants_write(&self) -> bool {
+        // encrypted records (or handshake messages) are waiting for the socket
+        self.conn.wants_write()
+    }
+
+    pub fn write_pending(&mut self) -> io::Result<()> {
+        // push out as many buffered records as the socket will take; WouldBlock once it won't take any more
+        while self.conn.wants_write() {
+            match self.conn.write_tls(&mut self.socket) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(_) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn shutdown(&mut self) {
+        // best effort: say goodbye properly if the socket has room for it
+        self.conn.send_close_notify();
+        let _ = self.write_pending();
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            // hand out anything that's already been decrypted before touching the socket
+            match self.conn.reader().read(buf) {
+                Ok(len) => return Ok(len), // 0 means the peer sent close_notify: a clean eof
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+            if self.conn.read_tls(&mut self.socket)? == 0 {
+                return Ok(0);
+            }
+            self.conn
+                .process_new_packets()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            // handshake replies have to go out even if we never have anything to say ourselves
+            match self.write_pending() {
+                Err(e) if e.kind() != io::ErrorKind::WouldBlock => return Err(e),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_vectored(&[io::IoSlice::new(buf)])
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        let written = self.conn.writer().write_vectored(bufs)?;
+        match self.write_pending() {
+            Err(e) if e.kind() != io::ErrorKind::WouldBlock => return Err(e),
+            _ => {}
+        }
+        if written == 0 && bufs.iter().any(|b| !b.is_empty()) {
+            return Err(io::ErrorKind::WouldBlock.into()); // rustls' buffer is full; wait for the socket to drain it
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()
+    }
+}
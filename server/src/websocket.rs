@@ -1,15 +1,20 @@
 // a blocking, asynchronous, single-threaded websocket server!
-// supports minimal http upgrade (no tls termination) and uses posix for fastliness.
+// supports minimal http upgrade (plus tls termination, with the tls feature) and uses posix for fastliness.
 use ringbuf::rb::local::LocalRb;
 use ringbuf::storage::Heap;
 use ringbuf::traits::Consumer;
 use ringbuf::traits::{Observer, Producer};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::io;
 use std::io::Read;
 use std::io::Write;
 use std::net::{self, TcpListener, TcpStream};
-use std::os::fd::AsRawFd;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
 pub struct ClientId(pub usize);
@@ -61,14 +66,245 @@ impl std::default::Default for HttpUpgradeChecklist {
 
 struct Client {
     id: ClientId,
-    socket: TcpStream,
+    socket: Transport,
     inbox: LocalRb<Heap<u8>>, // incoming buffer, consumed by the async eaters
-    outbox: LocalRb<Heap<u8>>, // outgoing buffer, flushed occasionally by the Server
+    outbox: VecDeque<OutgoingFrame>, // frames waiting to be written to the socket, oldest first
+    outbox_bytes: usize,      // total size of everything in the outbox
+    outbox_written: usize, // how much of the front frame has already gone out. a half-sent frame can't be coalesced away
+    outbox_popped: u64, // how many frames have ever left the outbox; turns the positions in coalesce_slots into indices
+    coalesce_slots: HashMap<u64, u64>, // coalesce key -> absolute position of the queued frame carrying that key
+    coalesced: u64, // frames replaced by a newer copy since the server last collected this
+    over_budget_since: Option<Instant>, // when the outbox last went over the byte budget, if it still is
+    write_interest: bool,               // whether the socket is registered with epoll for EPOLLOUT
+    input_pending: bool, // the inbox filled up before the socket ran dry, so there may be more to read without epoll saying so
+    request_path: Option<String>, // the path from the http request line
+    static_dir: Option<PathBuf>,
+    linger: bool, // an http response is going out; close once the outbox is empty
     closed: bool,
     control_state: ClientControlState,
     header_buffer: Vec<u8>,
     checklist: HttpUpgradeChecklist,
     frame: WebsocketFrameBuilder,
+    payload: Vec<u8>, // the unmasked payload of the frame currently being read
+    message: Vec<u8>, // data frames reassembled so far for the message currently being read
+    message_opcode: Option<u8>, // the opcode of the message being reassembled, if we're partway through one
+    max_message_size: usize, // reassembled messages larger than this are refused with MessageTooBig
+    greeted: bool,           // whether any message from the peer has decoded yet
+    greeting_mismatch: Option<(CloseCode, String)>,
+    closing: Option<Instant>, // if we've sent a close frame, the time after which we stop waiting for the peer's reply
+    close_timeout: Duration,
+    close_reason: Option<CloseReason>, // why this connection is (or will be) closed, handed to the disconnect callback
+    last_heard: Instant,               // the last time we got any bytes at all from the peer
+    next_ping: Instant,                // when the next heartbeat ping is due
+    ping_outstanding: Option<(u32, Instant)>, // (sequence number, send time) of the ping we're waiting on a pong for
+    ping_sequence: u32,
+    rtt: Option<Duration>, // smoothed round-trip time, once at least one pong has come back
+    rtt_fresh: bool,       // rtt changed since the server last collected it
+}
+
+enum Transport {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<crate::tls::TlsStream>),
+}
+
+impl Transport {
+    fn wants_write(&self) -> bool {
+        // true if the transport itself has bytes buffered for the socket, outside of the outbox
+        match self {
+            Self::Plain(_) => false,
+            #[cfg(feature = "tls")]
+            Self::Tls(tls) => tls.wants_write(),
+        }
+    }
+
+    fn shutdown(&mut self) {
+        match self {
+            Self::Plain(_) => {}
+            #[cfg(feature = "tls")]
+            Self::Tls(tls) => tls.shutdown(),
+        }
+    }
+}
+
+impl Read for Transport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(socket) => socket.read(buf),
+            #[cfg(feature = "tls")]
+            Self::Tls(tls) => tls.read(buf),
+        }
+    }
+}
+
+impl Write for Transport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(socket) => socket.write(buf),
+            #[cfg(feature = "tls")]
+            Self::Tls(tls) => tls.write(buf),
+        }
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        match self {
+            Self::Plain(socket) => socket.write_vectored(bufs),
+            #[cfg(feature = "tls")]
+            Self::Tls(tls) => tls.write_vectored(bufs),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(socket) => socket.flush(),
+            #[cfg(feature = "tls")]
+            Self::Tls(tls) => tls.flush(),
+        }
+    }
+}
+
+impl AsRawFd for Transport {
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            Self::Plain(socket) => socket.as_raw_fd(),
+            #[cfg(feature = "tls")]
+            Self::Tls(tls) => tls.socket().as_raw_fd(),
+        }
+    }
+}
+
+struct OutgoingFrame {
+    data: Rc<[u8]>, // shared, so a broadcast is only encoded once
+    key: Option<u64>,
+}
+
+/// Messages that can be coalesced in a client's send queue: if a message with the same key is still waiting
+/// to be sent, the new one replaces it in place rather than queueing behind it. Messages without a key are
+/// always delivered, in order.
+pub trait Coalesce {
+    fn coalesce_key(&self) -> Option<u64>;
+}
+
+/// Send queue statistics across every connected client.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct QueueMetrics {
+    pub queued_bytes: usize,       // waiting to be sent, summed over every client
+    pub queued_frames: usize,      // likewise
+    pub deepest_queue: usize,      // bytes queued for the single most backed-up client
+    pub coalesced: u64, // running total of frames replaced by a newer copy before they went out
+    pub overflow_disconnects: u64, // running total of clients dropped for staying over the byte budget
+}
+
+/// Status codes sent in websocket close frames (RFC 6455 section 7.4.1).
+#[repr(u16)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CloseCode {
+    Normal = 1000,
+    GoingAway = 1001,
+    ProtocolError = 1002,
+    UnsupportedData = 1003,
+    InvalidPayload = 1007,
+    PolicyViolation = 1008,
+    MessageTooBig = 1009,
+    InternalError = 1011,
+    TryAgainLater = 1013,
+}
+
+impl CloseCode {
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Normal => "normal closure",
+            Self::GoingAway => "going away",
+            Self::ProtocolError => "protocol error",
+            Self::UnsupportedData => "unsupported data",
+            Self::InvalidPayload => "invalid message",
+            Self::PolicyViolation => "policy violation",
+            Self::MessageTooBig => "message too big",
+            Self::InternalError => "internal error",
+            Self::TryAgainLater => "try again later",
+        }
+    }
+}
+
+// whether a peer is allowed to send this close code (RFC 6455 7.4). 1004-1006 and 1015 are reserved, 1016-2999
+// belong to future revisions of the spec, and 3000-4999 are for libraries and applications. 1012-1014 were
+// registered with IANA after the RFC went out, so they're fine too
+fn valid_close_code(code: u16) -> bool {
+    matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999)
+}
+
+/// Why a connection ended. Handed to the disconnect callback.
+#[derive(Clone, Debug)]
+pub struct CloseReason {
+    pub code: u16, // the status code from whichever close frame started the handshake; 1006 if there was no handshake
+    pub reason: String, // the utf-8 reason from that close frame, possibly empty
+    pub by_peer: bool, // true if the peer started the close (or just vanished), false if we did
+}
+
+impl CloseReason {
+    // the connection dropped without a close handshake. 1006 is reserved for exactly this and is never sent on the wire.
+    fn abnormal() -> Self {
+        Self {
+            code: 1006,
+            reason: "connection lost".to_string(),
+            by_peer: true,
+        }
+    }
+
+    /// True if the connection was lost (it dropped, timed out, or the peer went away) rather than deliberately
+    /// ended by either side. Lost connections are the ones worth resuming.
+    pub fn is_lost(&self) -> bool {
+        self.code == 1006 || self.code == CloseCode::GoingAway as u16
+    }
+}
+
+impl std::fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} by {} ({})",
+            if self.reason.is_empty() {
+                "closed"
+            } else {
+                &self.reason
+            },
+            if self.by_peer { "peer" } else { "server" },
+            self.code
+        )
+    }
+}
+
+/// Tunables for the websocket server.
+pub struct Settings {
+    pub max_message_size: usize, // the largest message (after reassembling fragments) a client may send
+    pub close_timeout: Duration, // how long to wait for the peer to answer our close frame before dropping the socket
+    pub heartbeat_interval: Duration, // how often upgraded clients are pinged
+    pub idle_timeout: Duration, // clients we haven't heard anything from (pongs included) for this long are dropped
+    pub max_queued_bytes: usize, // the send queue byte budget for each client
+    pub backpressure_grace: Duration, // how long a client may stay over max_queued_bytes before it's dropped
+    pub static_dir: Option<PathBuf>, // if set, plain GET requests for anything but /game are served from here
+    // how to turn away a peer whose very first message doesn't decode: it's most likely speaking some other version of
+    // the protocol, which deserves a better reason than InvalidPayload
+    pub greeting_mismatch: Option<(CloseCode, String)>,
+    #[cfg(feature = "tls")]
+    pub tls: Option<Arc<rustls::ServerConfig>>, // if set, every connection is tls (wss:// and https://)
+}
+
+impl std::default::Default for Settings {
+    fn default() -> Self {
+        Self {
+            max_message_size: 16384,
+            close_timeout: Duration::from_secs(2),
+            heartbeat_interval: Duration::from_secs(5),
+            idle_timeout: Duration::from_secs(15),
+            max_queued_bytes: 1 << 20,
+            backpressure_grace: Duration::from_secs(5),
+            static_dir: None,
+            greeting_mismatch: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
 }
 
 enum ClientControllerEvent<MessageType> {
@@ -78,13 +314,15 @@ enum ClientControllerEvent<MessageType> {
     ClientUpgraded(ClientId), // successful upgrade; more data may be needed (do not sleep)
 }
 
-#[derive(PartialEq, Copy, Clone, Debug)]
+#[derive(PartialEq, PartialOrd, Copy, Clone, Debug)]
 enum ClientControlState {
+    // ordered: every HTTP state comes before every websocket state
     HttpFirstLine,
     HttpHeaderName,
     HttpHeaderValue,
     HttpHeaderNameStartCheckShim, // ensure that the first byte of the header name is not whitespace: if it is, we're looking at the
     // empty line that terminates the request
+    HttpDone, // a response has been queued; anything else the peer sends is ignored
     WebSocketFirstTwo,
     WebSocketExtLength,
     WebSocketMasking,
@@ -125,36 +363,80 @@ impl std::default::Default for WebsocketFrameBuilder {
 }
 
 impl Client {
-    fn new_from(id: ClientId, socket: TcpStream) -> Self {
+    fn new_from(id: ClientId, socket: Transport, settings: &Settings) -> Self {
         Self {
             id,
             socket,
             inbox: LocalRb::new(4096),
-            outbox: LocalRb::new(4096),
+            outbox: VecDeque::new(),
+            outbox_bytes: 0,
+            outbox_written: 0,
+            outbox_popped: 0,
+            coalesce_slots: HashMap::new(),
+            coalesced: 0,
+            over_budget_since: None,
+            write_interest: false,
+            input_pending: false,
+            request_path: None,
+            static_dir: settings.static_dir.clone(),
+            linger: false,
             closed: false,
             control_state: ClientControlState::HttpFirstLine,
             header_buffer: Vec::new(),
             checklist: HttpUpgradeChecklist::default(),
             frame: WebsocketFrameBuilder::default(),
+            payload: Vec::new(),
+            message: Vec::new(),
+            message_opcode: None,
+            max_message_size: settings.max_message_size,
+            greeted: false,
+            greeting_mismatch: settings.greeting_mismatch.clone(),
+            closing: None,
+            close_timeout: settings.close_timeout,
+            close_reason: None,
+            last_heard: Instant::now(),
+            next_ping: Instant::now() + settings.heartbeat_interval,
+            ping_outstanding: None,
+            ping_sequence: 0,
+            rtt: None,
+            rtt_fresh: false,
         }
     }
 
     fn recvin(&mut self) {
         const BUF_SIZE: usize = 4096;
         let mut buffer = [0u8; BUF_SIZE];
-        while let Ok(len) = self.socket.read(&mut buffer) {
-            if len == 0 {
-                self.closed = true;
+        self.input_pending = false;
+        loop {
+            // never read more than the inbox can hold; anything left over stays put (in the kernel, or in the tls
+            // layer) until poll() has eaten some of the inbox. input_pending makes sure we come back for it
+            let space = self.inbox.vacant_len().min(BUF_SIZE);
+            if space == 0 {
+                self.input_pending = true;
                 break;
             }
-            if self.inbox.push_slice(&buffer[0..len]) < len {
-                println!("buffer overflow, closing connection");
+            let len = match self.socket.read(&mut buffer[0..space]) {
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    // reset, or garbage on a tls connection. either way it's over
+                    self.closed = true;
+                    if self.close_reason.is_none() {
+                        self.close_reason = Some(CloseReason::abnormal());
+                    }
+                    break;
+                }
+            };
+            if len == 0 {
                 self.closed = true;
+                if self.close_reason.is_none() {
+                    self.close_reason = Some(CloseReason::abnormal());
+                }
                 break;
             }
-            if len < BUF_SIZE {
-                break;
-            }
+            self.inbox.push_slice(&buffer[0..len]);
+            self.last_heard = Instant::now();
         }
     }
 
@@ -165,6 +447,7 @@ impl Client {
         if let WebSocketFirstTwo = self.control_state {
             let mut buf = [0u8; 2];
             if self.fill_or_incomplete(&mut buf) {
+                self.frame = WebsocketFrameBuilder::default();
                 self.frame.fin = (buf[0] & 0b1000_0000) >> 7 == 1;
                 self.frame.rsv = (buf[0] & 0b0111_0000) >> 4;
                 self.frame.opcode = buf[0] & 0b0000_1111;
@@ -189,61 +472,73 @@ impl Client {
             } else {
                 self.control_state = WebSocketMasking;
             }
+            if self.control_state == WebSocketMasking {
+                // the header is complete enough to validate: reject bad frames before we read any of their payload
+                if let Err(code) = self.check_frame() {
+                    self.fail(code);
+                    return ClientControllerEvent::Sleep;
+                }
+            }
         }
         if let WebSocketMasking = self.control_state {
-            if self.frame.mask {
-                let mut buf = [0u8; 4];
-                if self.fill_or_incomplete(&mut buf) {
-                    self.frame.mask_key = Some(buf);
-                    self.control_state = WebSocketPayload;
-                }
-            } else {
+            let mut buf = [0u8; 4];
+            if self.fill_or_incomplete(&mut buf) {
+                self.frame.mask_key = Some(buf);
+                self.payload.clear();
                 self.control_state = WebSocketPayload;
             }
         }
         if let WebSocketPayload = self.control_state {
-            if self.inbox.occupied_len() >= self.frame.length() {
-                if self.frame.length() > 2048 {
-                    self.closed = true;
-                    return ClientControllerEvent::Sleep;
-                }
-                let mut buffer = vec![0u8; self.frame.length()];
-                self.inbox.read(&mut buffer).unwrap();
-                for i in 0..buffer.len() {
+            // payloads are read incrementally, so frames (and messages) can be much larger than the inbox
+            let remaining = self.frame.length() - self.payload.len();
+            let amount = remaining.min(self.inbox.occupied_len());
+            if amount > 0 {
+                let start = self.payload.len();
+                self.payload.resize(start + amount, 0);
+                self.inbox.read_exact(&mut self.payload[start..]).unwrap();
+                let mask_key = self.frame.mask_key.unwrap(); // check_frame guarantees that client frames are masked
+                for (i, byte) in self.payload[start..].iter_mut().enumerate() {
                     // TODO: SIMD optimize this
-                    buffer[i] ^= self.frame.mask_key.unwrap()[i % 4];
+                    *byte ^= mask_key[(start + i) % 4];
                 }
+            }
+            if self.payload.len() == self.frame.length() {
                 self.control_state = WebSocketFirstTwo;
-                // TODO: don't unwrap here
-                if self.frame.opcode == 2 {
-                    // binary, which is the only valid encoding for our purposes
-                    let message_decode: MessageType = bitcode::decode(&buffer).unwrap();
-                    return ClientControllerEvent::MessageReceived(self.id, message_decode);
-                } else if self.frame.opcode == 8 {
-                    // close
-                    self.send_raw(&[0b1000_1000, 0]); // don't bother with the status code, just send a quick close frame and be done
-                    self.closed = true;
-                    return ClientControllerEvent::Sleep; // sleep immediately so the connection can be closed
-                } else if self.frame.opcode == 9 {
-                    self.send_raw(&[0b1000_1010, 0]); // PONG, unmasked 0-byte body
-                } // no support for sending pings as they're useless
-                return ClientControllerEvent::MaybeUnfinished;
+                return self.frame_complete();
             }
         }
 
-        // HTTP upgrade logic
+        // HTTP logic
         // this is about the simplest an HTTP server can be while still maintaining some semblance of usefulness.
-        // there's only one valid request line ("GET /game HTTP/1.1\r\n").
-        // it rejects bad connections extremely quickly and has very little
+        // only GET is supported. /game must upgrade to a websocket; anything else is a static file, if there's
+        // a static directory to serve from. it rejects bad connections extremely quickly and has very little
         // allocation overhead.
+        if let HttpDone = self.control_state {
+            self.inbox.clear();
+            return ClientControllerEvent::Sleep;
+        }
         if let HttpFirstLine = self.control_state {
-            if let Some(val) = self.match_or_incomplete(b"GET /game HTTP/1.1\r\n") {
-                if val {
-                    self.control_state = HttpHeaderName;
-                } else {
-                    self.error_abort(
-                        b"Only GET requests to /game (no trailing slash) are permitted.",
-                    );
+            if let Some(line) = self.match_until_or_incomplete(b'\n') {
+                let line = byte_trim(&line);
+                let mut parts = line.split(|b| *b == b' ');
+                match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                    (Some(b"GET"), Some(path), Some(b"HTTP/1.1" | b"HTTP/1.0"), None) => {
+                        match str::from_utf8(path) {
+                            Ok(path) => {
+                                self.request_path = Some(path.to_string());
+                                self.control_state = HttpHeaderName;
+                            }
+                            Err(_) => self.error_abort(b"Bad request path."),
+                        }
+                    }
+                    (Some(_), Some(_), Some(_), None) => {
+                        self.http_response(
+                            "405 Method Not Allowed",
+                            "text/plain",
+                            b"Only GET requests are permitted.",
+                        );
+                    }
+                    _ => self.error_abort(b"Malformed request line."),
                 }
             }
         }
@@ -251,12 +546,14 @@ impl Client {
             if let Some(w) = self.check_whitespace() {
                 // we're DONE!
                 if w {
-                    if self.checklist.will_upgrade() {
+                    if self.request_path.as_deref() != Some("/game") {
+                        self.serve_static();
+                    } else if self.checklist.will_upgrade() {
                         self.upgrade();
                         self.control_state = WebSocketFirstTwo;
                         return ClientControllerEvent::ClientUpgraded(self.id);
                     } else {
-                        self.error_abort(b"All requests must upgrade to websockets.")
+                        self.error_abort(b"All requests to /game must upgrade to websockets.")
                     }
                 } else {
                     self.control_state = HttpHeaderName;
@@ -298,6 +595,19 @@ impl Client {
                 }
             }
         }
+        if self.control_state < WebSocketFirstTwo
+            && self.control_state != HttpDone
+            && self.inbox.vacant_len() == 0
+        {
+            // the inbox is full and we still can't make sense of it: some header line is enormous.
+            // we'd never get any further, and epoll would keep waking us up about the unread data
+            self.http_response(
+                "431 Request Header Fields Too Large",
+                "text/plain",
+                b"Request header too large.",
+            );
+            return ClientControllerEvent::Sleep;
+        }
         if old_control_state != self.control_state {
             // if there was a state change, we don't want to sleep
             ClientControllerEvent::MaybeUnfinished
@@ -306,11 +616,304 @@ impl Client {
         }
     }
 
+    fn check_frame(&self) -> Result<(), CloseCode> {
+        // validate a frame header against RFC 6455 and our own limits
+        if self.frame.rsv != 0 {
+            return Err(CloseCode::ProtocolError); // we never negotiate extensions, so the reserved bits must be clear
+        }
+        if !self.frame.mask {
+            return Err(CloseCode::ProtocolError); // every client -> server frame must be masked
+        }
+        match self.frame.opcode {
+            0 => {
+                // continuation
+                if self.message_opcode.is_none() {
+                    return Err(CloseCode::ProtocolError); // nothing to continue
+                }
+            }
+            1 => {
+                return Err(CloseCode::UnsupportedData); // text frames are useless to us; everything is bitcode
+            }
+            2 => {
+                if self.message_opcode.is_some() {
+                    return Err(CloseCode::ProtocolError); // a new message can't start until the last one is finished
+                }
+            }
+            8..=10 => {
+                // control frames can be interleaved with fragments, but can't themselves be fragmented
+                if !self.frame.fin || self.frame.length() > 125 {
+                    return Err(CloseCode::ProtocolError);
+                }
+                return Ok(());
+            }
+            _ => {
+                return Err(CloseCode::ProtocolError);
+            }
+        }
+        if self.message.len() + self.frame.length() > self.max_message_size {
+            return Err(CloseCode::MessageTooBig);
+        }
+        Ok(())
+    }
+
+    fn frame_complete<MessageType: bitcode::DecodeOwned>(
+        &mut self,
+    ) -> ClientControllerEvent<MessageType> {
+        // a frame has been fully read into self.payload; do something with it
+        match self.frame.opcode {
+            0 | 2 => {
+                if self.frame.opcode == 2 {
+                    self.message_opcode = Some(self.frame.opcode);
+                }
+                self.message.extend_from_slice(&self.payload);
+                if self.frame.fin {
+                    self.message_opcode = None;
+                    let message = std::mem::take(&mut self.message);
+                    if self.closing.is_some() {
+                        // we've already said goodbye; anything else the peer sends before its close reply is dropped
+                        return ClientControllerEvent::MaybeUnfinished;
+                    }
+                    match bitcode::decode(&message) {
+                        Ok(message_decode) => {
+                            self.greeted = true;
+                            return ClientControllerEvent::MessageReceived(self.id, message_decode);
+                        }
+                        Err(_) => {
+                            match self.greeting_mismatch.take() {
+                                Some((code, reason)) if !self.greeted => {
+                                    self.fail_because(code, &reason)
+                                }
+                                _ => self.fail(CloseCode::InvalidPayload),
+                            }
+                            return ClientControllerEvent::Sleep;
+                        }
+                    }
+                }
+            }
+            8 => {
+                // close. the body is optional, but if it's there it's a 2-byte status code followed by a utf-8 reason
+                let (code, reason) = match self.payload.len() {
+                    0 => (None, String::new()),
+                    1 => {
+                        self.fail(CloseCode::ProtocolError);
+                        return ClientControllerEvent::Sleep;
+                    }
+                    _ => match String::from_utf8(self.payload[2..].to_vec()) {
+                        Ok(reason) => (
+                            Some(u16::from_be_bytes([self.payload[0], self.payload[1]])),
+                            reason,
+                        ),
+                        Err(_) => {
+                            self.fail(CloseCode::InvalidPayload);
+                            return ClientControllerEvent::Sleep;
+                        }
+                    },
+                };
+                if code.is_some_and(|code| !valid_close_code(code)) {
+                    // a reserved or unassigned code is a protocol error, and not a reason we should believe
+                    self.fail(CloseCode::ProtocolError);
+                    return ClientControllerEvent::Sleep;
+                }
+                if self.closing.is_none() {
+                    // the peer started the handshake, so we finish it by echoing the status code
+                    match code {
+                        Some(code) => {
+                            let code = code.to_be_bytes();
+                            self.send_raw(&[0b1000_1000, 2, code[0], code[1]]);
+                        }
+                        None => self.send_raw(&[0b1000_1000, 0]),
+                    }
+                    self.close_reason = Some(CloseReason {
+                        code: code.unwrap_or(1005), // 1005: no status code was present
+                        reason,
+                        by_peer: true,
+                    });
+                } // otherwise this is the reply to our close frame, and the handshake is done
+                self.closed = true;
+                return ClientControllerEvent::Sleep; // sleep immediately so the connection can be closed
+            }
+            9 if self.closing.is_some() => {} // nothing may be sent after a close frame, pongs included
+            9 => {
+                // PONG, unmasked, echoing the ping's body as the spec requires
+                let payload = std::mem::take(&mut self.payload);
+                self.send_raw(&[0b1000_1010, payload.len() as u8]);
+                self.send_raw(&payload);
+            }
+            10 => {
+                // PONG. only ones answering our current ping count toward rtt; unsolicited pongs are allowed and ignored
+                if let Some((sequence, sent)) = self.ping_outstanding {
+                    if self.payload[..] == sequence.to_be_bytes() {
+                        let sample = sent.elapsed();
+                        self.rtt = Some(match self.rtt {
+                            Some(rtt) => (rtt * 7 + sample) / 8, // same smoothing as tcp's srtt
+                            None => sample,
+                        });
+                        self.rtt_fresh = true;
+                        self.ping_outstanding = None;
+                    }
+                }
+            }
+            _ => {}
+        }
+        ClientControllerEvent::MaybeUnfinished
+    }
+
+    fn fail(&mut self, code: CloseCode) {
+        self.fail_because(code, code.description());
+    }
+
+    fn fail_because(&mut self, code: CloseCode, reason: &str) {
+        // something about the websocket stream is broken: tell the client why and hang up.
+        // we can't trust anything else on the stream, so there's no waiting for a close reply
+        println!("failing websocket connection: {:?} ({})", code, reason);
+        if self.closing.is_none() {
+            self.send_close(code as u16, reason);
+        }
+        self.closed = true;
+    }
+
+    fn start_close(&mut self, code: u16, reason: &str) {
+        // begin the close handshake. the socket is kept around until the peer replies or close_timeout runs out
+        if self.closed || self.closing.is_some() {
+            return;
+        }
+        if self.control_state < ClientControlState::WebSocketFirstTwo {
+            self.closed = true; // never upgraded, so there's nobody to shake hands with
+            return;
+        }
+        self.send_close(code, reason);
+        self.closing = Some(Instant::now() + self.close_timeout);
+    }
+
+    fn send_close(&mut self, code: u16, reason: &str) {
+        // control frames are capped at 125 bytes, 2 of which are the code. don't cut a utf-8 character in half
+        let mut len = reason.len().min(123);
+        while !reason.is_char_boundary(len) {
+            len -= 1;
+        }
+        let reason = &reason[..len];
+        let code_bytes = code.to_be_bytes();
+        self.send_raw(&[
+            0b1000_1000,
+            2 + reason.len() as u8,
+            code_bytes[0],
+            code_bytes[1],
+        ]);
+        self.send_raw(reason.as_bytes());
+        self.close_reason = Some(CloseReason {
+            code,
+            reason: reason.to_string(),
+            by_peer: false,
+        });
+    }
+
+    fn heartbeat(&mut self, now: Instant, settings: &Settings) {
+        // reap the client if it's gone quiet, otherwise ping it if a ping is due
+        if now.duration_since(self.last_heard) >= settings.idle_timeout {
+            if self.can_send() {
+                self.send_close(CloseCode::GoingAway as u16, "idle timeout"); // probably nobody's listening, but just in case
+            } else {
+                self.close_reason = Some(CloseReason {
+                    code: 1006,
+                    reason: "idle timeout".to_string(),
+                    by_peer: false,
+                });
+            }
+            self.closed = true;
+            return;
+        }
+        if self.can_send() && now >= self.next_ping {
+            // a ping that was never answered is just replaced; the idle timeout takes care of peers that stop answering
+            self.ping_sequence = self.ping_sequence.wrapping_add(1);
+            let sequence = self.ping_sequence.to_be_bytes();
+            self.send_raw(&[0b1000_1001, sequence.len() as u8]);
+            self.send_raw(&sequence);
+            self.ping_outstanding = Some((self.ping_sequence, now));
+            self.next_ping = now + settings.heartbeat_interval;
+        }
+    }
+
+    fn next_deadline(&self, settings: &Settings) -> Instant {
+        // the soonest time one of this client's timers needs attention
+        let mut deadline = self.last_heard + settings.idle_timeout;
+        if let Some(closing) = self.closing {
+            deadline = deadline.min(closing);
+        } else if self.can_send() {
+            deadline = deadline.min(self.next_ping);
+        }
+        if let Some(since) = self.over_budget_since {
+            deadline = deadline.min(since + settings.backpressure_grace);
+        }
+        deadline
+    }
+
+    fn can_send(&self) -> bool {
+        // true if this client can still accept messages: it's upgraded and nobody has started closing it
+        !self.closed
+            && self.closing.is_none()
+            && self.control_state >= ClientControlState::WebSocketFirstTwo
+    }
+
     fn error_abort(&mut self, body: &[u8]) {
         println!("error aborting: {}", str::from_utf8(body).unwrap());
-        self.send_raw(b"HTTP/1.1 400 Bad Request\r\n\r\n");
+        self.http_response("400 Bad Request", "text/plain", body);
+    }
+
+    fn http_response(&mut self, status: &str, content_type: &str, body: &[u8]) {
+        // queue a complete response, then hang up once it's been sent. there's no keep-alive
+        self.send_raw(
+            format!(
+                "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                content_type,
+                body.len()
+            )
+            .as_bytes(),
+        );
         self.send_raw(body);
-        self.closed = true;
+        self.control_state = ClientControlState::HttpDone;
+        self.inbox.clear();
+        self.linger = true;
+    }
+
+    fn serve_static(&mut self) {
+        // a plain GET for anything but /game: look it up in the static directory
+        let Some(root) = self.static_dir.clone() else {
+            self.http_response(
+                "404 Not Found",
+                "text/plain",
+                b"Not found. The game lives at /game.",
+            );
+            return;
+        };
+        let path = self.request_path.take().unwrap_or_default();
+        let path = path.split('?').next().unwrap().trim_start_matches('/'); // query strings are ignored
+        let path = Path::new(if path.is_empty() { "index.html" } else { path });
+        // only plain names: no .., no absolute paths, nothing that could escape the static directory
+        if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+            self.http_response("404 Not Found", "text/plain", b"Not found.");
+            return;
+        }
+        let mut file = root.join(path);
+        if file.is_dir() {
+            file.push("index.html");
+        }
+        let content_type = match file.extension().and_then(|e| e.to_str()) {
+            Some("html") => "text/html; charset=utf-8",
+            Some("js") => "text/javascript; charset=utf-8",
+            Some("css") => "text/css; charset=utf-8",
+            Some("wasm") => "application/wasm", // browsers refuse to stream-compile wasm served as anything else
+            Some("svg") => "image/svg+xml",
+            Some("png") => "image/png",
+            Some("json") => "application/json",
+            Some("ico") => "image/x-icon",
+            Some("txt") => "text/plain; charset=utf-8",
+            _ => "application/octet-stream",
+        };
+        match std::fs::read(&file) {
+            Ok(body) => self.http_response("200 OK", content_type, &body),
+            Err(_) => self.http_response("404 Not Found", "text/plain", b"Not found."),
+        }
     }
 
     fn upgrade(&mut self) {
@@ -320,23 +923,6 @@ impl Client {
         self.send_raw(b"\r\n\r\n");
     }
 
-    fn match_or_incomplete(&mut self, data: &[u8]) -> Option<bool> {
-        if data.len() <= self.inbox.occupied_len() {
-            for i in 0..data.len() {
-                if let Some(d) = self.inbox.try_pop() {
-                    if d != data[i] {
-                        return Some(false);
-                    }
-                } else {
-                    return Some(false);
-                }
-            }
-            Some(true)
-        } else {
-            None
-        }
-    }
-
     fn fill_or_incomplete(&mut self, buf: &mut [u8]) -> bool {
         if self.inbox.occupied_len() >= buf.len() {
             self.inbox.read(buf).unwrap();
@@ -346,9 +932,40 @@ impl Client {
     }
 
     fn send_raw(&mut self, data: &[u8]) {
-        while let Err(_) = self.outbox.write(data) {
-            let _ = self.try_flush();
+        self.enqueue(Rc::from(data), None);
+    }
+
+    fn enqueue(&mut self, data: Rc<[u8]>, key: Option<u64>) {
+        // queue some bytes for the socket. nothing is written here; try_flush does that
+        if let Some(key) = key {
+            if let Some(&position) = self.coalesce_slots.get(&key) {
+                let index = (position - self.outbox_popped) as usize;
+                if index > 0 || self.outbox_written == 0 {
+                    let frame = &mut self.outbox[index];
+                    self.outbox_bytes = self.outbox_bytes - frame.data.len() + data.len();
+                    frame.data = data;
+                    self.coalesced += 1;
+                    return;
+                }
+            }
+            self.coalesce_slots
+                .insert(key, self.outbox_popped + self.outbox.len() as u64);
         }
+        self.outbox_bytes += data.len();
+        self.outbox.push_back(OutgoingFrame { data, key });
+    }
+
+    fn pop_sent(&mut self) {
+        // the front frame has been completely written
+        let frame = self.outbox.pop_front().unwrap();
+        if let Some(key) = frame.key {
+            if self.coalesce_slots.get(&key) == Some(&self.outbox_popped) {
+                self.coalesce_slots.remove(&key);
+            }
+        }
+        self.outbox_popped += 1;
+        self.outbox_written = 0;
+        self.outbox_bytes -= frame.data.len();
     }
 
     fn match_until_or_incomplete(&mut self, until: u8) -> Option<Vec<u8>> {
@@ -356,7 +973,8 @@ impl Client {
         let mut count = 0;
         loop {
             let mut buffer = [0u8; 1];
-            if self.inbox.read(&mut buffer).ok()? != 1 {
+            // an empty ring reports WouldBlock; either way, rewind below and wait for more data
+            if !matches!(self.inbox.read(&mut buffer), Ok(1)) {
                 break;
             }
             if buffer[0] == until {
@@ -379,21 +997,48 @@ impl Client {
     }
 
     fn try_flush(&mut self) -> io::Result<bool> {
-        // try to send all the data in outbox through the socket. returns false if the outbox could not be fully
+        // try to send everything in the outbox through the socket. returns false if the outbox could not be fully
         // emptied, otherwise true.
-        const BUF_SIZE: usize = 1024;
-        let mut buffer = [0u8; BUF_SIZE];
-        loop {
-            let amnt = self.outbox.peek_slice(&mut buffer);
-            let write_count = self.socket.write(&buffer[0..amnt])?;
-            self.outbox.skip(write_count);
-            if write_count < BUF_SIZE {
-                return Ok(false);
+        const MAX_SLICES: usize = 64;
+        if let Err(e) = self.socket.flush() {
+            // records the transport buffered last time go first
+            if e.kind() != io::ErrorKind::WouldBlock {
+                return Err(e);
+            }
+            return Ok(false);
+        }
+        while !self.outbox.is_empty() {
+            let mut slices = [io::IoSlice::new(&[]); MAX_SLICES];
+            let mut count = 0;
+            for (i, frame) in self.outbox.iter().take(MAX_SLICES).enumerate() {
+                let start = if i == 0 { self.outbox_written } else { 0 };
+                slices[i] = io::IoSlice::new(&frame.data[start..]);
+                count += 1;
             }
-            if amnt < BUF_SIZE {
-                return Ok(true);
+            let mut written = match self.socket.write_vectored(&slices[..count]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    if self.linger {
+                        self.last_heard = Instant::now(); // a big download is progress, not idleness
+                    }
+                    written
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            while written > 0 {
+                let left = self.outbox[0].data.len() - self.outbox_written;
+                if written >= left {
+                    written -= left;
+                    self.pop_sent();
+                } else {
+                    self.outbox_written += written;
+                    written = 0;
+                }
             }
         }
+        Ok(true)
     }
 
     fn require_trimmed(&mut self) -> bool {
@@ -417,13 +1062,73 @@ impl Client {
 }
 
 pub struct Server {
-    // misleadingly, the select_bucket is actually passed into poll(), not select(). oops.
-    select_bucket: Vec<libc::pollfd>, // cached so we don't have to allocate memory often; this is regenerated when clients are added or dropped
-    // the first socket in the select_bucket is always the server socket.
-    bucket_clean: bool, // if false, we need to regenerate the bucket before running select
+    epoll: OwnedFd,
+    events: Vec<libc::epoll_event>, // cached so we don't have to allocate every time we wait
+    waker: Waker,
     clients: HashMap<ClientId, Client>,
     top_id: ClientId,
     server_socket: TcpListener,
+    settings: Settings,
+    rtt_updates: Vec<(ClientId, Duration)>, // fresh round-trip times since the last drain_rtt_updates
+    coalesced: u64,                         // see QueueMetrics
+    overflow_disconnects: u64,              // likewise
+}
+
+// epoll tokens. client ids start at 1, so they can be used directly
+const LISTENER_TOKEN: u64 = 0;
+const WAKER_TOKEN: u64 = u64::MAX;
+
+const MAX_WAIT: Duration = Duration::from_secs(1); // do_poll always returns at least this often, even if nothing happens
+
+/// Wakes the server out of `do_poll`, so it notices outgoing messages promptly. Cheap to clone and fine to hand
+/// to other threads. Wakes are coalesced until the server next wakes up, so calling this for every message is fine.
+#[derive(Clone)]
+pub struct Waker(Arc<WakerInner>);
+
+struct WakerInner {
+    fd: OwnedFd, // an eventfd
+    pending: AtomicBool,
+}
+
+impl Waker {
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self(Arc::new(WakerInner {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            pending: AtomicBool::new(false),
+        })))
+    }
+
+    pub fn wake(&self) {
+        if !self.0.pending.swap(true, Ordering::SeqCst) {
+            let one = 1u64;
+            unsafe {
+                libc::write(
+                    self.0.fd.as_raw_fd(),
+                    &one as *const u64 as *const libc::c_void,
+                    8,
+                );
+            }
+        }
+    }
+
+    fn reset(&self) {
+        // empty the eventfd before clearing the flag. the other way around, a wake landing in between would write,
+        // have its write eaten by our read, and leave the flag set, so every later wake would skip its write
+        let mut count = 0u64;
+        while unsafe {
+            libc::read(
+                self.0.fd.as_raw_fd(),
+                &mut count as *mut u64 as *mut libc::c_void,
+                8,
+            )
+        } > 0
+        {}
+        self.0.pending.store(false, Ordering::Release);
+    }
 }
 
 #[derive(Debug)]
@@ -434,72 +1139,219 @@ pub enum Event<Message> {
 }
 
 impl Server {
-    pub fn new(address: impl net::ToSocketAddrs) -> io::Result<Self> {
+    pub fn new(
+        address: impl net::ToSocketAddrs,
+        settings: Settings,
+        waker: Waker,
+    ) -> io::Result<Self> {
         let s = TcpListener::bind(address)?;
         s.set_nonblocking(true)?;
+        let epoll = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if epoll < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let epoll = unsafe { OwnedFd::from_raw_fd(epoll) };
+        epoll_ctl(
+            &epoll,
+            libc::EPOLL_CTL_ADD,
+            s.as_raw_fd(),
+            libc::EPOLLIN,
+            LISTENER_TOKEN,
+        )?;
+        epoll_ctl(
+            &epoll,
+            libc::EPOLL_CTL_ADD,
+            waker.0.fd.as_raw_fd(),
+            libc::EPOLLIN,
+            WAKER_TOKEN,
+        )?;
         Ok(Self {
-            select_bucket: vec![],
-            bucket_clean: false, // always regenerate on the first go
+            epoll,
+            events: Vec::with_capacity(64),
+            waker,
             clients: HashMap::new(),
             top_id: ClientId(0),
             server_socket: s,
+            settings,
+            rtt_updates: vec![],
+            coalesced: 0,
+            overflow_disconnects: 0,
         })
     }
 
-    fn generate_bucket(&mut self) {
-        self.select_bucket.clear();
-        self.select_bucket.push(libc::pollfd {
-            fd: self.server_socket.as_raw_fd(),
-            events: libc::POLLIN,
-            revents: 0,
-        });
-        for (_, client) in self.clients.iter() {
-            self.select_bucket.push(libc::pollfd {
-                fd: client.socket.as_raw_fd(),
-                events: libc::POLLIN,
-                revents: 0,
-            });
+    /// A handle that can wake this server out of `do_poll` from another thread.
+    pub fn waker(&self) -> Waker {
+        self.waker.clone()
+    }
+
+    fn wrap(&self, socket: TcpStream) -> io::Result<Transport> {
+        #[cfg(feature = "tls")]
+        if let Some(config) = &self.settings.tls {
+            return Ok(Transport::Tls(Box::new(crate::tls::TlsStream::new(
+                config.clone(),
+                socket,
+            )?)));
+        }
+        Ok(Transport::Plain(socket))
+    }
+
+    fn wait_timeout(&self, now: Instant) -> i32 {
+        // how long epoll can sleep before one of the clients' timers (heartbeat, idle, close, backpressure) is due
+        let mut deadline = now + MAX_WAIT;
+        for (_, cl) in self.clients.iter() {
+            if cl.closed || cl.input_pending {
+                return 0;
+            }
+            deadline = deadline.min(cl.next_deadline(&self.settings));
         }
-        self.bucket_clean = true;
+        let wait = deadline.saturating_duration_since(now);
+        wait.as_micros().div_ceil(1000) as i32 // round up, so we don't wake a hair early and spin
     }
 
+    /// Flush outgoing data, run timers, then sleep until a socket is ready, the waker is poked, or a timer is due,
+    /// and handle whatever woke us. Messages queued with send_to/broadcast are flushed on the next call.
     pub fn do_poll<IncomingMessageType: bitcode::DecodeOwned, Context>(
         &mut self,
         context: &mut Context,
         mut message_callback: impl FnMut(ClientId, IncomingMessageType, &mut Server, &mut Context) -> (),
         mut connect_callback: impl FnMut(ClientId, &mut Server, &mut Context) -> (),
-        mut disconnect_callback: impl FnMut(ClientId, &mut Context) -> (),
+        mut disconnect_callback: impl FnMut(ClientId, CloseReason, &mut Context) -> (),
     ) {
-        if !self.bucket_clean {
-            self.generate_bucket();
+        let now = Instant::now();
+        for (_, cl) in self.clients.iter_mut() {
+            if !cl.closed && cl.closing.is_none() {
+                cl.heartbeat(now, &self.settings);
+            }
+            if cl.rtt_fresh {
+                cl.rtt_fresh = false;
+                self.rtt_updates.push((cl.id, cl.rtt.unwrap()));
+            }
+            self.coalesced += std::mem::take(&mut cl.coalesced);
+            if cl.try_flush().is_err() {
+                cl.closed = true; // the socket is dead; there's no point in waiting for anything else
+            }
+            if cl.linger && cl.outbox.is_empty() && !cl.socket.wants_write() {
+                cl.closed = true; // the http response is all sent
+            }
+            if cl.outbox_bytes > self.settings.max_queued_bytes && !cl.linger {
+                let since = *cl.over_budget_since.get_or_insert(now);
+                if !cl.closed && now.duration_since(since) >= self.settings.backpressure_grace {
+                    // it isn't keeping up, and a close frame would just sit at the back of the queue. hang up
+                    println!("client {:?} fell too far behind; dropping it", cl.id);
+                    cl.close_reason = Some(CloseReason {
+                        code: CloseCode::PolicyViolation as u16,
+                        reason: "send queue overflow".to_string(),
+                        by_peer: false,
+                    });
+                    cl.closed = true;
+                    self.overflow_disconnects += 1;
+                }
+            } else {
+                cl.over_budget_since = None;
+            }
+            if let Some(deadline) = cl.closing {
+                if now >= deadline {
+                    cl.closed = true; // the peer never answered our close frame
+                }
+            }
+            if !cl.closed {
+                // only ask about writability while there's something left to write, or epoll would never sleep
+                let want_write = !cl.outbox.is_empty() || cl.socket.wants_write();
+                if want_write != cl.write_interest {
+                    // keep the same set the socket was added with, or a half-closed peer stops being noticed
+                    let events = if want_write {
+                        libc::EPOLLIN | libc::EPOLLRDHUP | libc::EPOLLOUT
+                    } else {
+                        libc::EPOLLIN | libc::EPOLLRDHUP
+                    };
+                    if epoll_ctl(
+                        &self.epoll,
+                        libc::EPOLL_CTL_MOD,
+                        cl.socket.as_raw_fd(),
+                        events,
+                        cl.id.0 as u64,
+                    )
+                    .is_err()
+                    {
+                        cl.closed = true;
+                    } else {
+                        cl.write_interest = want_write;
+                    }
+                }
+            }
         }
+        self.clients.retain(|_, client| {
+            // prune closed clients. dropping the socket closes it, which also takes it out of the epoll set
+            if client.closed {
+                client.socket.shutdown();
+                disconnect_callback(
+                    client.id,
+                    client
+                        .close_reason
+                        .take()
+                        .unwrap_or_else(CloseReason::abnormal),
+                    context,
+                );
+            }
+            !client.closed
+        });
+
+        let timeout = self.wait_timeout(Instant::now());
+        let count = unsafe {
+            libc::epoll_wait(
+                self.epoll.as_raw_fd(),
+                self.events.as_mut_ptr(),
+                self.events.capacity() as i32,
+                timeout,
+            )
+        };
+        // an error here is almost certainly EINTR; treat it as a spurious wakeup
         unsafe {
-            libc::poll(
-                self.select_bucket.as_mut_ptr(),
-                self.select_bucket.len() as u64,
-                3,
-            );
+            self.events.set_len(count.max(0) as usize);
         }
         let mut dispatch_queue = vec![]; // TODO: cache this to avoid allocating
         let mut dispatch_queue_connections = vec![]; // TODO: cache this to avoid allocating
-        for (index, (_, client)) in self.clients.iter_mut().enumerate() {
-            if 0 != (self.select_bucket[index + 1].revents & libc::POLLIN) {
-                // received data!
-                client.recvin();
-                loop {
-                    match client.poll::<IncomingMessageType>() {
-                        // poll until the client controller sleeps
-                        ClientControllerEvent::Sleep => {
-                            break;
-                        }
-                        ClientControllerEvent::MessageReceived(id, message) => {
-                            dispatch_queue.push((id, message));
-                        }
-                        ClientControllerEvent::ClientUpgraded(id) => {
-                            dispatch_queue_connections.push(id);
-                        }
-                        _ => {}
+        let mut accept = false;
+        let mut readable = vec![]; // TODO: cache this to avoid allocating
+        for event in self.events.iter() {
+            match event.u64 {
+                LISTENER_TOKEN => accept = true,
+                WAKER_TOKEN => self.waker.reset(),
+                token => {
+                    // writability needs no handling here: the flush at the top of the next do_poll takes care of it
+                    if event.events
+                        & (libc::EPOLLIN | libc::EPOLLRDHUP | libc::EPOLLHUP | libc::EPOLLERR)
+                            as u32
+                        != 0
+                    {
+                        readable.push(ClientId(token as usize));
+                    }
+                }
+            }
+        }
+        for (id, cl) in self.clients.iter() {
+            if cl.input_pending && !readable.contains(id) {
+                readable.push(*id);
+            }
+        }
+        for id in readable {
+            let Some(client) = self.clients.get_mut(&id) else {
+                continue;
+            };
+            client.recvin();
+            loop {
+                match client.poll::<IncomingMessageType>() {
+                    // poll until the client controller sleeps
+                    ClientControllerEvent::Sleep => {
+                        break;
+                    }
+                    ClientControllerEvent::MessageReceived(id, message) => {
+                        dispatch_queue.push((id, message));
                     }
+                    ClientControllerEvent::ClientUpgraded(id) => {
+                        dispatch_queue_connections.push(id);
+                    }
+                    _ => {}
                 }
             }
         }
@@ -509,26 +1361,30 @@ impl Server {
         for id in dispatch_queue_connections {
             connect_callback(id, self, context);
         }
-        for (_, cl) in self.clients.iter_mut() {
-            cl.try_flush().unwrap();
-        }
-        self.clients.retain(|_, client| {
-            // prune closed clients
-            if client.closed {
-                disconnect_callback(client.id, context);
-                self.bucket_clean = false;
-            }
-            !client.closed
-        });
-        if 0 != (self.select_bucket[0].revents & libc::POLLIN) {
+        if accept {
             loop {
                 if let Ok((client, _)) = self.server_socket.accept() {
                     client.set_nonblocking(true).unwrap();
                     client.set_nodelay(true).unwrap();
+                    let Ok(client) = self.wrap(client) else {
+                        continue;
+                    };
                     self.top_id.0 += 1;
-                    self.clients
-                        .insert(self.top_id, Client::new_from(self.top_id, client));
-                    self.bucket_clean = false;
+                    if epoll_ctl(
+                        &self.epoll,
+                        libc::EPOLL_CTL_ADD,
+                        client.as_raw_fd(),
+                        libc::EPOLLIN | libc::EPOLLRDHUP,
+                        self.top_id.0 as u64,
+                    )
+                    .is_err()
+                    {
+                        continue; // dropping it hangs up
+                    }
+                    self.clients.insert(
+                        self.top_id,
+                        Client::new_from(self.top_id, client, &self.settings),
+                    );
                 } else {
                     break;
                 }
@@ -536,30 +1392,37 @@ impl Server {
         }
     }
 
-    fn make_header(length: usize) -> Vec<u8> {
-        let mut header = Vec::with_capacity(14);
-        header.push(0b1000_0010);
+    fn encode_frame<MessageType: bitcode::Encode>(message: &MessageType) -> Rc<[u8]> {
+        // a complete binary frame: header, then the bitcode-encoded message
+        let enc = bitcode::encode(message);
+        let length = enc.len();
+        let mut frame = Vec::with_capacity(10 + length);
+        frame.push(0b1000_0010);
         if length > 125 {
             if length < 65536 {
-                header.extend_from_slice(&(length as u16).to_be_bytes());
+                frame.push(126);
+                frame.extend_from_slice(&(length as u16).to_be_bytes());
             } else {
-                header.extend_from_slice(&length.to_be_bytes());
+                frame.push(127);
+                frame.extend_from_slice(&(length as u64).to_be_bytes());
             }
         } else {
-            header.push(length as u8); // mask is 0 automatically
+            frame.push(length as u8); // mask is 0 automatically
         }
-        header
+        frame.extend_from_slice(&enc);
+        frame.into()
     }
 
-    pub fn send_to<MessageType: bitcode::Encode>(
+    pub fn send_to<MessageType: bitcode::Encode + Coalesce>(
         &mut self,
         client: ClientId,
         message: MessageType,
     ) {
         if let Some(client) = self.clients.get_mut(&client) {
-            let enc = &bitcode::encode(&message);
-            client.send_raw(&Self::make_header(enc.len()));
-            client.send_raw(enc);
+            if !client.can_send() {
+                return;
+            }
+            client.enqueue(Self::encode_frame(&message), message.coalesce_key());
         } else {
             println!(
                 "WARNING: attempted to send a message to a disconnected client! this is not fatal."
@@ -567,19 +1430,88 @@ impl Server {
         }
     }
 
-    pub fn broadcast<MessageType: bitcode::Encode>(&mut self, message: MessageType) {
-        let enc = bitcode::encode(&message);
-        let header = Self::make_header(enc.len());
+    pub fn broadcast<MessageType: bitcode::Encode + Coalesce>(&mut self, message: MessageType) {
+        let frame = Self::encode_frame(&message);
+        let key = message.coalesce_key();
         for (_, client) in self.clients.iter_mut() {
-            client.send_raw(&header);
-            client.send_raw(&enc);
+            if client.can_send() {
+                client.enqueue(frame.clone(), key);
+            }
+        }
+    }
+
+    /// A snapshot of how backed up the send queues are.
+    pub fn queue_metrics(&self) -> QueueMetrics {
+        let mut metrics = QueueMetrics {
+            coalesced: self.coalesced,
+            overflow_disconnects: self.overflow_disconnects,
+            ..Default::default()
+        };
+        for (_, cl) in self.clients.iter() {
+            metrics.queued_bytes += cl.outbox_bytes;
+            metrics.queued_frames += cl.outbox.len();
+            metrics.deepest_queue = metrics.deepest_queue.max(cl.outbox_bytes);
+        }
+        metrics
+    }
+
+    /// Start the close handshake with a client: send it a close frame with a status code and a (short) reason.
+    /// The client is dropped, and the disconnect callback fired, once it replies or the close timeout runs out.
+    pub fn close(&mut self, id: ClientId, code: CloseCode, reason: &str) {
+        if let Some(cl) = self.clients.get_mut(&id) {
+            cl.start_close(code as u16, reason);
+        }
+    }
+
+    /// The smoothed round-trip time to a client, if it's answered any heartbeat pings yet.
+    pub fn rtt(&self, id: ClientId) -> Option<Duration> {
+        self.clients.get(&id).and_then(|cl| cl.rtt)
+    }
+
+    /// Round-trip times that have changed since the last call, for forwarding to whoever cares.
+    pub fn drain_rtt_updates(&mut self) -> std::vec::Drain<'_, (ClientId, Duration)> {
+        self.rtt_updates.drain(..)
+    }
+
+    /// Close every client, as with `close`.
+    pub fn close_all(&mut self, code: CloseCode, reason: &str) {
+        for (_, cl) in self.clients.iter_mut() {
+            cl.start_close(code as u16, reason);
+        }
+    }
+}
+
+impl From<common::comms::DisconnectReason> for CloseCode {
+    fn from(reason: common::comms::DisconnectReason) -> Self {
+        use common::comms::DisconnectReason::*;
+        match reason {
+            GameOver => Self::Normal,
+            ServerFull => Self::TryAgainLater,
+            KickedByAdmin => Self::PolicyViolation,
+            HandshakeFailed | IncompatibleProtocol => Self::ProtocolError,
         }
     }
+}
+
+fn epoll_ctl(epoll: &OwnedFd, op: i32, fd: RawFd, events: i32, token: u64) -> io::Result<()> {
+    let mut event = libc::epoll_event {
+        events: events as u32,
+        u64: token,
+    };
+    if unsafe { libc::epoll_ctl(epoll.as_raw_fd(), op, fd, &mut event) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
 
-    pub fn close(&mut self, id: ClientId) {
-        let cl = self.clients.get_mut(&id).unwrap();
-        cl.closed = true;
-        cl.send_raw(&[136, 0]);
+impl Coalesce for common::comms::ServerMessage {
+    fn coalesce_key(&self) -> Option<u64> {
+        match self {
+            // a newer snapshot is a delta against the client's last ack, same as the one it's replacing,
+            // so it carries everything the old one did
+            Self::Snapshot { .. } => Some(0),
+            _ => None,
+        }
     }
 }
 
@@ -594,3 +1526,162 @@ impl Into<ClientId> for common::PlayerId {
         ClientId(self.0 as usize)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an upgraded client with nothing behind it: frames are pushed straight into the inbox, and whatever the client
+    // wants to say back piles up in the outbox, since nothing ever flushes it
+    fn upgraded_client() -> Client {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let socket = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut client =
+            Client::new_from(ClientId(0), Transport::Plain(socket), &Settings::default());
+        client.control_state = ClientControlState::WebSocketFirstTwo;
+        client
+    }
+
+    // a masked client -> server frame. payloads are small enough to never need an extended length
+    fn frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask_key = [0x12, 0x34, 0x56, 0x78];
+        let mut frame = vec![(fin as u8) << 7 | opcode, 0b1000_0000 | payload.len() as u8];
+        frame.extend_from_slice(&mask_key);
+        frame.extend(
+            payload
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ mask_key[i % 4]),
+        );
+        frame
+    }
+
+    fn close_frame(code: u16) -> Vec<u8> {
+        frame(true, 8, &code.to_be_bytes())
+    }
+
+    // feed some bytes in and run the state machine until it runs dry, like the server's event loop does
+    fn receive(client: &mut Client, bytes: &[u8]) -> Vec<String> {
+        client.inbox.push_slice(bytes);
+        let mut messages = Vec::new();
+        loop {
+            match client.poll::<String>() {
+                ClientControllerEvent::Sleep => break,
+                ClientControllerEvent::MessageReceived(_, message) => messages.push(message),
+                _ => {}
+            }
+        }
+        messages
+    }
+
+    fn sent(client: &Client) -> Vec<u8> {
+        client
+            .outbox
+            .iter()
+            .flat_map(|frame| frame.data.iter().copied())
+            .collect()
+    }
+
+    #[test]
+    fn close_codes() {
+        for code in [
+            1000, 1001, 1002, 1003, 1007, 1011, 1012, 1014, 3000, 4000, 4999,
+        ] {
+            assert!(valid_close_code(code), "{code} should be allowed");
+        }
+        for code in [0, 999, 1004, 1005, 1006, 1015, 1016, 2999, 5000, u16::MAX] {
+            assert!(!valid_close_code(code), "{code} should be rejected");
+        }
+    }
+
+    #[test]
+    fn fragmented_message() {
+        let message = bitcode::encode(&"pieces of a message".to_string());
+        let (first, rest) = message.split_at(3);
+        let (middle, last) = rest.split_at(5);
+        let mut client = upgraded_client();
+        assert!(receive(&mut client, &frame(false, 2, first)).is_empty());
+        assert!(receive(&mut client, &frame(false, 0, middle)).is_empty());
+        assert_eq!(
+            receive(&mut client, &frame(true, 0, last)),
+            ["pieces of a message"]
+        );
+        assert!(client.message.is_empty() && client.message_opcode.is_none());
+
+        // a whole message split across reads, at awkward places
+        let whole = [frame(false, 2, first), frame(true, 0, rest)].concat();
+        assert!(receive(&mut client, &whole[..1]).is_empty());
+        assert!(receive(&mut client, &whole[1..9]).is_empty());
+        assert_eq!(receive(&mut client, &whole[9..]), ["pieces of a message"]);
+        assert!(!client.closed);
+    }
+
+    #[test]
+    fn control_frame_between_fragments() {
+        let message = bitcode::encode(&"interrupted".to_string());
+        let (first, last) = message.split_at(4);
+        let mut client = upgraded_client();
+        let bytes = [
+            frame(false, 2, first),
+            frame(true, 9, b"ping!"),
+            frame(true, 0, last),
+        ]
+        .concat();
+        assert_eq!(receive(&mut client, &bytes), ["interrupted"]);
+        assert_eq!(sent(&client), [&[0b1000_1010, 5][..], b"ping!"].concat()); // the ping was answered in between
+        assert!(!client.closed);
+    }
+
+    #[test]
+    fn bad_fragments() {
+        let protocol_error = |client: &Client| {
+            client.closed && sent(client).starts_with(&[0b1000_1000, 16, 0x03, 0xea])
+            // 1002, "protocol error"
+        };
+
+        // a continuation with nothing to continue
+        let mut client = upgraded_client();
+        receive(&mut client, &frame(true, 0, b"stray"));
+        assert!(protocol_error(&client));
+
+        // a new message before the last one was finished
+        let mut client = upgraded_client();
+        receive(
+            &mut client,
+            &[frame(false, 2, b"one"), frame(true, 2, b"two")].concat(),
+        );
+        assert!(protocol_error(&client));
+
+        // a fragmented control frame
+        let mut client = upgraded_client();
+        receive(
+            &mut client,
+            &[frame(false, 2, b"one"), frame(false, 9, b"ping!")].concat(),
+        );
+        assert!(protocol_error(&client));
+    }
+
+    #[test]
+    fn close_handshake() {
+        // a valid code is echoed back and handed on as the reason
+        let mut client = upgraded_client();
+        receive(&mut client, &close_frame(4000));
+        assert!(client.closed);
+        assert_eq!(sent(&client), [0b1000_1000, 2, 0x0f, 0xa0]);
+        let reason = client.close_reason.unwrap();
+        assert!(reason.code == 4000 && reason.by_peer);
+
+        // reserved codes can't be sent by a peer: that's a protocol error, not a reason to pass along
+        for code in [1004, 1005, 1006, 1015] {
+            let mut client = upgraded_client();
+            receive(&mut client, &close_frame(code));
+            assert!(client.closed);
+            assert!(
+                sent(&client).starts_with(&[0b1000_1000, 16, 0x03, 0xea]),
+                "{code} wasn't refused"
+            );
+            let reason = client.close_reason.unwrap();
+            assert!(reason.code == 1002 && !reason.by_peer);
+        }
+    }
+}